@@ -24,6 +24,7 @@ fn main() {
     scope(|scope| {
         scope.register(Interrupt::INT0, int0);
 
+        #[allow(clippy::never_loop)]
         loop {
             // Idle loop
             break;
@@ -5,6 +5,8 @@
 pub use cortex_m_rt::{self, interrupt};
 
 #[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u16)]
 pub enum interrupt {
     INT0,
     INT1,
@@ -0,0 +1,281 @@
+//! Closure adapters for common interrupt handling patterns.
+
+/// Wraps `f` so that it is only invoked on every `n`th call.
+///
+/// This is useful for reducing the processing overhead of high-rate interrupt sources like
+/// encoder edges, when it is enough to react to batches of events. An `n` of 0 is treated like 1.
+///
+/// # Examples
+///
+/// ```
+/// use irq::{coalesce, handler};
+///
+/// let mut batches = 0;
+/// handler!(encoder = coalesce(64, || batches += 1));
+/// ```
+pub fn coalesce<F>(n: u32, mut f: F) -> impl FnMut()
+where
+    F: FnMut(),
+{
+    let mut count = 0;
+    move || {
+        count += 1;
+        if count >= n {
+            count = 0;
+            f();
+        }
+    }
+}
+
+/// Wraps `f` so that it is invoked at most once every `min_ticks` ticks.
+///
+/// Like [`coalesce`], this reduces the processing overhead of high-rate interrupt sources, but
+/// limits the rate of invocations instead of counting events. `now` reads a free-running timer
+/// counting in arbitrary ticks (it may wrap around). The first call always invokes `f`. Later calls
+/// only invoke it if at least `min_ticks` ticks have passed since the last invocation, and are
+/// ignored otherwise.
+///
+/// # Examples
+///
+/// ```
+/// use irq::{coalesce_interval, handler};
+/// # fn timer_ticks() -> u32 { 0 }
+///
+/// let mut updates = 0;
+/// handler!(encoder = coalesce_interval(timer_ticks, 1000, || updates += 1));
+/// ```
+///
+/// [`coalesce`]: fn.coalesce.html
+pub fn coalesce_interval<T, F>(mut now: T, min_ticks: u32, mut f: F) -> impl FnMut()
+where
+    T: FnMut() -> u32,
+    F: FnMut(),
+{
+    let mut last: Option<u32> = None;
+    move || {
+        let time = now();
+        let due = match last {
+            None => true,
+            Some(last) => time.wrapping_sub(last) >= min_ticks,
+        };
+        if due {
+            last = Some(time);
+            f();
+        }
+    }
+}
+
+/// Wraps `f` so that it is only invoked when `guard` returns `true`.
+///
+/// The guard is evaluated on every call. When it returns `false`, the interrupt is ignored. This
+/// allows cheaply disarming a handler (for example while a measurement is not running) without
+/// changing the handler itself or its registration.
+///
+/// The guard runs as part of the registered handler, not in the veneer. A disarmed interrupt is
+/// therefore simply ignored, and is not subject to the `unregistered => behavior;` configured in
+/// [`scoped_interrupts!`], which only applies when no handler is registered at all. To handle an
+/// interrupt differently while disarmed, do so from the guard before returning `false`.
+///
+/// # Examples
+///
+/// ```
+/// use core::sync::atomic::{AtomicBool, Ordering};
+/// use irq::{guarded, handler};
+///
+/// let armed = AtomicBool::new(false);
+/// let mut captures = 0;
+/// handler!(capture = guarded(|| armed.load(Ordering::Relaxed), || captures += 1));
+/// ```
+///
+/// [`scoped_interrupts!`]: macro.scoped_interrupts.html
+pub fn guarded<G, F>(mut guard: G, mut f: F) -> impl FnMut()
+where
+    G: FnMut() -> bool,
+    F: FnMut(),
+{
+    move || {
+        if guard() {
+            f();
+        }
+    }
+}
+
+/// Wraps `f` so that it is only invoked on stable level transitions of a bouncing input.
+///
+/// This is meant to be registered for the edge interrupt of a GPIO pin connected to a button or
+/// switch. On every interrupt, `read` samples the level of the pin, and `now` reads a free-running
+/// timer counting in arbitrary ticks (it may wrap around). The new level is passed to `f` if it
+/// differs from the last reported level and at least `settle` ticks have passed since the last
+/// reported transition. Edges caused by contact bounce right after a transition are ignored.
+///
+/// The first interrupt is always reported. Since the input is only sampled when an edge interrupt
+/// fires, a bounce that ends in the opposite level within `settle` ticks of a transition is only
+/// reported on the next edge.
+///
+/// # Examples
+///
+/// ```
+/// use irq::{debounce, handler};
+/// # fn pin_is_high() -> bool { true }
+/// # fn timer_ticks() -> u32 { 0 }
+///
+/// let mut presses = 0;
+/// handler!(button = debounce(pin_is_high, timer_ticks, 1000, |high| if high { presses += 1 }));
+/// ```
+pub fn debounce<R, T, F>(mut read: R, mut now: T, settle: u32, mut f: F) -> impl FnMut()
+where
+    R: FnMut() -> bool,
+    T: FnMut() -> u32,
+    F: FnMut(bool),
+{
+    let mut last: Option<(bool, u32)> = None;
+    move || {
+        let level = read();
+        let time = now();
+        let report = match last {
+            None => true,
+            Some((last_level, since)) => level != last_level && time.wrapping_sub(since) >= settle,
+        };
+        if report {
+            last = Some((level, time));
+            f(level);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::cell::Cell;
+
+    #[test]
+    fn coalesce_every_nth() {
+        let mut calls = 0;
+        let mut f = coalesce(3, || calls += 1);
+        for _ in 0..10 {
+            f();
+        }
+        drop(f);
+        assert_eq!(calls, 3);
+    }
+
+    #[test]
+    fn coalesce_one() {
+        let mut calls = 0;
+        let mut f = coalesce(1, || calls += 1);
+        f();
+        f();
+        drop(f);
+        assert_eq!(calls, 2);
+    }
+
+    #[test]
+    fn coalesce_zero() {
+        let mut calls = 0;
+        let mut f = coalesce(0, || calls += 1);
+        f();
+        f();
+        drop(f);
+        assert_eq!(calls, 2);
+    }
+
+    #[test]
+    fn coalesce_interval_min_ticks() {
+        let time = Cell::new(100);
+        let mut calls = 0;
+        let mut f = coalesce_interval(|| time.get(), 10, || calls += 1);
+        for t in 100..125 {
+            time.set(t);
+            f();
+        }
+        drop(f);
+        // Invoked at 100, 110 and 120.
+        assert_eq!(calls, 3);
+    }
+
+    #[test]
+    fn coalesce_interval_wraparound() {
+        let time = Cell::new(u32::MAX - 2);
+        let mut calls = 0;
+        let mut f = coalesce_interval(|| time.get(), 10, || calls += 1);
+
+        f();
+        time.set(3);
+        f();
+        time.set(8);
+        f();
+
+        drop(f);
+        assert_eq!(calls, 2);
+    }
+
+    #[test]
+    fn guarded_skips_when_false() {
+        let armed = Cell::new(false);
+        let mut calls = 0;
+        let mut f = guarded(|| armed.get(), || calls += 1);
+        f();
+        armed.set(true);
+        f();
+        f();
+        armed.set(false);
+        f();
+        drop(f);
+        assert_eq!(calls, 2);
+    }
+
+    #[test]
+    fn debounce_ignores_bounce() {
+        let level = Cell::new(true);
+        let time = Cell::new(0);
+        let mut reported = Vec::new();
+        let mut f = debounce(
+            || level.get(),
+            || time.get(),
+            10,
+            |high| reported.push(high),
+        );
+
+        // Press, followed by bouncing edges within the settle time.
+        f();
+        for t in 1..5 {
+            level.set(t % 2 == 0);
+            time.set(t);
+            f();
+        }
+
+        // Release after the contacts have settled.
+        level.set(false);
+        time.set(20);
+        f();
+        level.set(true);
+        time.set(22);
+        f();
+
+        drop(f);
+        assert_eq!(reported, [true, false]);
+    }
+
+    #[test]
+    fn debounce_wraparound() {
+        let level = Cell::new(true);
+        let time = Cell::new(u32::MAX - 2);
+        let mut reported = Vec::new();
+        let mut f = debounce(
+            || level.get(),
+            || time.get(),
+            10,
+            |high| reported.push(high),
+        );
+
+        f();
+        level.set(false);
+        time.set(3);
+        f();
+        time.set(8);
+        f();
+
+        drop(f);
+        assert_eq!(reported, [true, false]);
+    }
+}
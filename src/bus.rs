@@ -0,0 +1,293 @@
+//! A broadcast channel delivering every event to multiple subscribers.
+
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::mem::MaybeUninit;
+use core::ptr;
+use core::sync::atomic::{self, AtomicUsize, Ordering};
+
+/// A broadcast channel that fans out events from one interrupt handler to several subscribers.
+///
+/// Events are stored in a ring buffer with room for `N` events. Every [`Subscriber`] has its own
+/// cursor into the ring, so each of them sees every event, independently of the others. This
+/// allows several modules to react to the same interrupt in thread context, without having to
+/// chain their handlers in the interrupt handler itself.
+///
+/// Publishing never blocks. If a subscriber falls behind by more than `N` events, the oldest
+/// events it has not received yet are overwritten, and it skips ahead to the oldest event still
+/// available. The number of skipped events is reported by [`Subscriber::missed`].
+///
+/// `N` must be a power of two.
+///
+/// # Examples
+///
+/// ```
+/// use irq::{handler, scope, scoped_interrupts, EventBus};
+/// use mock_pac::interrupt;
+///
+/// scoped_interrupts! {
+///     enum Interrupt {
+///         INT0,
+///     }
+///
+///     use #[interrupt];
+/// }
+///
+/// # fn read_buttons() -> u8 { 0 }
+/// fn main() {
+///     let mut bus = EventBus::<u8, 8>::new();
+///     let (mut publisher, bus) = bus.split();
+///     let mut ui = bus.subscribe();
+///     let mut logger = bus.subscribe();
+///
+///     handler!(buttons = || publisher.publish(read_buttons()));
+///
+///     scope(|scope| {
+///         scope.register(Interrupt::INT0, buttons);
+///
+///         loop {
+///             while let Some(state) = ui.recv() {
+///                 // Update the UI...
+/// # let _ = state;
+///             }
+///             while let Some(state) = logger.recv() {
+///                 // Log the button state...
+/// # let _ = state;
+///             }
+/// # break;
+///         }
+///     });
+/// }
+/// ```
+///
+/// [`Subscriber`]: struct.Subscriber.html
+/// [`Subscriber::missed`]: struct.Subscriber.html#method.missed
+pub struct EventBus<T, const N: usize> {
+    slots: [Slot<T>; N],
+    /// Number of published events. The most recent event lives in slot `head % N`.
+    head: AtomicUsize,
+}
+
+struct Slot<T> {
+    value: UnsafeCell<MaybeUninit<T>>,
+    /// Number of the event that was last started to be written into this slot.
+    started: AtomicUsize,
+    /// Number of the event that was last completely written into this slot.
+    published: AtomicUsize,
+}
+
+impl<T> Slot<T> {
+    /// Used to initialize the slot array, so every use creating a new slot is intended.
+    #[allow(clippy::declare_interior_mutable_const)]
+    const NEW: Self = Self {
+        value: UnsafeCell::new(MaybeUninit::uninit()),
+        started: AtomicUsize::new(0),
+        published: AtomicUsize::new(0),
+    };
+}
+
+// Safety: Values of type `T` are sent from the publisher to the subscribers. The slots are only
+// accessed by following the seqlock-like protocol below.
+unsafe impl<T: Send, const N: usize> Sync for EventBus<T, N> {}
+
+impl<T, const N: usize> EventBus<T, N> {
+    /// Forces a compile-time error when `N` is not a power of two.
+    ///
+    /// This ensures that the event numbers map to consecutive slots, even when they wrap around.
+    const POWER_OF_TWO: () = assert!(
+        N.is_power_of_two(),
+        "`EventBus` capacity must be a power of two"
+    );
+
+    /// Creates a new, empty event bus.
+    pub const fn new() -> Self {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::POWER_OF_TWO;
+        Self {
+            slots: [Slot::NEW; N],
+            head: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl<T: Copy, const N: usize> EventBus<T, N> {
+    /// Splits off the publishing half of the bus.
+    ///
+    /// Subscribers can be created from the returned shared reference using [`subscribe`].
+    ///
+    /// [`subscribe`]: #method.subscribe
+    pub fn split(&mut self) -> (Publisher<'_, T, N>, &Self) {
+        (Publisher { bus: self }, self)
+    }
+
+    /// Creates a new subscriber.
+    ///
+    /// The subscriber only receives events published after this call.
+    pub fn subscribe(&self) -> Subscriber<'_, T, N> {
+        Subscriber {
+            bus: self,
+            cursor: self.head.load(Ordering::Acquire),
+            missed: 0,
+        }
+    }
+}
+
+impl<T, const N: usize> Default for EventBus<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const N: usize> fmt::Debug for EventBus<T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EventBus")
+            .field("events", &self.head.load(Ordering::Relaxed))
+            .finish()
+    }
+}
+
+/// The publishing half of an [`EventBus`].
+///
+/// [`EventBus`]: struct.EventBus.html
+#[derive(Debug)]
+pub struct Publisher<'a, T, const N: usize> {
+    bus: &'a EventBus<T, N>,
+}
+
+impl<'a, T: Copy, const N: usize> Publisher<'a, T, N> {
+    /// Publishes `value` to all subscribers.
+    pub fn publish(&mut self, value: T) {
+        let bus = self.bus;
+        let next = bus.head.load(Ordering::Relaxed).wrapping_add(1);
+        let slot = &bus.slots[next % N];
+
+        slot.started.store(next, Ordering::Relaxed);
+        atomic::fence(Ordering::Release);
+
+        unsafe {
+            ptr::write_volatile(slot.value.get(), MaybeUninit::new(value));
+        }
+
+        slot.published.store(next, Ordering::Release);
+        bus.head.store(next, Ordering::Release);
+    }
+}
+
+/// A subscriber of an [`EventBus`].
+///
+/// [`EventBus`]: struct.EventBus.html
+#[derive(Debug)]
+pub struct Subscriber<'a, T, const N: usize> {
+    bus: &'a EventBus<T, N>,
+    /// Number of the last event received.
+    cursor: usize,
+    /// Number of events that were overwritten before they could be received.
+    missed: usize,
+}
+
+impl<'a, T: Copy, const N: usize> Subscriber<'a, T, N> {
+    /// Receives the oldest event this subscriber has not received yet.
+    ///
+    /// Returns `None` if all published events have already been received.
+    pub fn recv(&mut self) -> Option<T> {
+        loop {
+            let head = self.bus.head.load(Ordering::Acquire);
+            let behind = head.wrapping_sub(self.cursor);
+            if behind == 0 {
+                return None;
+            }
+
+            if behind > N {
+                // The events after the cursor were overwritten already.
+                self.missed = self.missed.saturating_add(behind - N);
+                self.cursor = head.wrapping_sub(N);
+            }
+
+            let expected = self.cursor.wrapping_add(1);
+            let slot = &self.bus.slots[expected % N];
+            if slot.published.load(Ordering::Acquire) != expected {
+                // The publisher has lapped us and overwrote the slot.
+                continue;
+            }
+
+            // This may race with the publisher overwriting the slot. In that case, the value is
+            // discarded below without being used.
+            let value = unsafe { ptr::read_volatile(slot.value.get()) };
+
+            atomic::fence(Ordering::Acquire);
+            if slot.started.load(Ordering::Relaxed) == expected {
+                self.cursor = expected;
+                return Some(unsafe { value.assume_init() });
+            }
+        }
+    }
+
+    /// Returns the number of events that were overwritten before this subscriber could receive
+    /// them.
+    pub fn missed(&self) -> usize {
+        self.missed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty() {
+        let mut bus = EventBus::<u32, 4>::new();
+        let (_, bus) = bus.split();
+        let mut sub = bus.subscribe();
+        assert_eq!(sub.recv(), None);
+        assert_eq!(sub.missed(), 0);
+    }
+
+    #[test]
+    fn fan_out() {
+        let mut bus = EventBus::<u32, 4>::new();
+        let (mut publisher, bus) = bus.split();
+        let mut a = bus.subscribe();
+        publisher.publish(1);
+        let mut b = bus.subscribe();
+        publisher.publish(2);
+
+        assert_eq!(a.recv(), Some(1));
+        assert_eq!(a.recv(), Some(2));
+        assert_eq!(a.recv(), None);
+        assert_eq!(b.recv(), Some(2));
+        assert_eq!(b.recv(), None);
+    }
+
+    #[test]
+    fn overrun() {
+        let mut bus = EventBus::<u32, 4>::new();
+        let (mut publisher, bus) = bus.split();
+        let mut sub = bus.subscribe();
+        for i in 0..10 {
+            publisher.publish(i);
+        }
+
+        assert_eq!(sub.recv(), Some(6));
+        assert_eq!(sub.missed(), 6);
+        assert_eq!(sub.recv(), Some(7));
+        assert_eq!(sub.recv(), Some(8));
+        assert_eq!(sub.recv(), Some(9));
+        assert_eq!(sub.recv(), None);
+    }
+
+    #[test]
+    fn sequence_wraparound() {
+        let mut bus = EventBus::<u32, 2>::new();
+        bus.head = AtomicUsize::new(usize::MAX - 1);
+        let (mut publisher, bus) = bus.split();
+        let mut sub = bus.subscribe();
+        for i in 0..3 {
+            publisher.publish(i);
+        }
+
+        assert_eq!(sub.recv(), Some(1));
+        assert_eq!(sub.recv(), Some(2));
+        assert_eq!(sub.recv(), None);
+        assert_eq!(sub.missed(), 1);
+    }
+}
@@ -0,0 +1,269 @@
+//! A single-value channel that always holds the most recent value.
+
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::mem::MaybeUninit;
+use core::ptr;
+use core::sync::atomic::{self, AtomicUsize, Ordering};
+
+/// A channel holding only the most recently written value.
+///
+/// This is useful for passing sensor readings or other samples from an interrupt handler to
+/// application code that only cares about the newest value. Writing never blocks and always
+/// overwrites the previous value, and reading always returns a coherent (not torn) value.
+///
+/// A `Latest` is split into a [`LatestWriter`] and a [`LatestReader`] using [`split`]. The
+/// writer and the reader can be used from any two interrupt priorities (or from thread mode),
+/// regardless of which one preempts the other.
+///
+/// # Examples
+///
+/// ```
+/// use irq::{handler, scope, scoped_interrupts, Latest};
+/// use mock_pac::interrupt;
+///
+/// scoped_interrupts! {
+///     enum Interrupt {
+///         INT0,
+///     }
+///
+///     use #[interrupt];
+/// }
+///
+/// # fn read_adc() -> u16 { 0 }
+/// fn main() {
+///     let mut channel = Latest::new();
+///     let (mut writer, mut reader) = channel.split();
+///
+///     handler!(adc = || writer.write(read_adc()));
+///
+///     scope(|scope| {
+///         scope.register(Interrupt::INT0, adc);
+///
+///         loop {
+///             if let Some(sample) = reader.fresh() {
+///                 // Run the control loop with the newest sample.
+/// # let _: u16 = sample;
+///             }
+/// # break;
+///         }
+///     });
+/// }
+/// ```
+///
+/// [`LatestWriter`]: struct.LatestWriter.html
+/// [`LatestReader`]: struct.LatestReader.html
+/// [`split`]: #method.split
+pub struct Latest<T> {
+    /// Two slots, so that the writer never writes to the slot a reader might be copying from.
+    slots: UnsafeCell<[MaybeUninit<T>; 2]>,
+    /// Number of completed writes. The most recent value lives in slot `published % 2`.
+    published: AtomicUsize,
+    /// Number of the write that was last started. Lets readers detect that the slot they were
+    /// reading from was overwritten.
+    started: AtomicUsize,
+}
+
+// Safety: Values of type `T` are sent from the writer to the reader. The slots are only accessed
+// by following the seqlock-like protocol below.
+unsafe impl<T: Send> Sync for Latest<T> {}
+
+/// Returns the number of the write following write number `n`.
+///
+/// 0 is skipped when wrapping around, since that indicates an empty channel. 2 refers to the same
+/// slot as 0.
+fn successor(n: usize) -> usize {
+    match n.wrapping_add(1) {
+        0 => 2,
+        next => next,
+    }
+}
+
+impl<T> Latest<T> {
+    /// Creates a new, empty channel.
+    pub const fn new() -> Self {
+        Self {
+            slots: UnsafeCell::new([MaybeUninit::uninit(), MaybeUninit::uninit()]),
+            published: AtomicUsize::new(0),
+            started: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl<T: Copy> Latest<T> {
+    /// Splits the channel into its writing and reading half.
+    pub fn split(&mut self) -> (LatestWriter<'_, T>, LatestReader<'_, T>) {
+        let seen = self.published.load(Ordering::Relaxed);
+        (
+            LatestWriter { latest: self },
+            LatestReader { latest: self, seen },
+        )
+    }
+
+    fn read(&self) -> Option<(T, usize)> {
+        loop {
+            let published = self.published.load(Ordering::Acquire);
+            if published == 0 {
+                return None;
+            }
+
+            // This may race with a writer that has already overwritten the slot again. In that
+            // case, the value is discarded below without being used.
+            let slots = self.slots.get() as *const MaybeUninit<T>;
+            let value = unsafe { ptr::read_volatile(slots.add(published % 2)) };
+
+            atomic::fence(Ordering::Acquire);
+            let started = self.started.load(Ordering::Relaxed);
+
+            // The writer only starts writing to the slot we just read from with the second write
+            // after `published`.
+            if started == published || started == successor(published) {
+                return Some((unsafe { value.assume_init() }, published));
+            }
+        }
+    }
+}
+
+impl<T> Default for Latest<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> fmt::Debug for Latest<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Latest")
+            .field("writes", &self.published.load(Ordering::Relaxed))
+            .finish()
+    }
+}
+
+/// The writing half of a [`Latest`] channel.
+///
+/// [`Latest`]: struct.Latest.html
+#[derive(Debug)]
+pub struct LatestWriter<'a, T> {
+    latest: &'a Latest<T>,
+}
+
+impl<'a, T: Copy> LatestWriter<'a, T> {
+    /// Replaces the value in the channel with `value`.
+    pub fn write(&mut self, value: T) {
+        let latest = self.latest;
+
+        let next = successor(latest.published.load(Ordering::Relaxed));
+
+        latest.started.store(next, Ordering::Relaxed);
+        atomic::fence(Ordering::Release);
+
+        unsafe {
+            let slots = latest.slots.get() as *mut MaybeUninit<T>;
+            ptr::write_volatile(slots.add(next % 2), MaybeUninit::new(value));
+        }
+
+        latest.published.store(next, Ordering::Release);
+    }
+}
+
+/// The reading half of a [`Latest`] channel.
+///
+/// [`Latest`]: struct.Latest.html
+#[derive(Debug)]
+pub struct LatestReader<'a, T> {
+    latest: &'a Latest<T>,
+    /// Write number of the last value returned by `fresh`.
+    seen: usize,
+}
+
+impl<'a, T: Copy> LatestReader<'a, T> {
+    /// Returns the most recently written value.
+    ///
+    /// Returns `None` if no value has been written yet.
+    pub fn latest(&self) -> Option<T> {
+        self.latest.read().map(|(value, _)| value)
+    }
+
+    /// Returns the most recently written value if it was written after the last call to `fresh`.
+    ///
+    /// Returns `None` if no new value has been written since then.
+    pub fn fresh(&mut self) -> Option<T> {
+        if self.latest.published.load(Ordering::Relaxed) == self.seen {
+            return None;
+        }
+
+        let (value, published) = self.latest.read()?;
+        self.seen = published;
+        Some(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty() {
+        let mut latest = Latest::<u32>::new();
+        let (_, mut reader) = latest.split();
+        assert_eq!(reader.latest(), None);
+        assert_eq!(reader.fresh(), None);
+    }
+
+    #[test]
+    fn overwrite() {
+        let mut latest = Latest::new();
+        let (mut writer, mut reader) = latest.split();
+
+        writer.write(1);
+        writer.write(2);
+        assert_eq!(reader.latest(), Some(2));
+        writer.write(3);
+        assert_eq!(reader.latest(), Some(3));
+        assert_eq!(reader.latest(), Some(3));
+        assert_eq!(reader.fresh(), Some(3));
+    }
+
+    #[test]
+    fn fresh() {
+        let mut latest = Latest::new();
+        let (mut writer, mut reader) = latest.split();
+
+        writer.write([1, 2, 3]);
+        assert_eq!(reader.fresh(), Some([1, 2, 3]));
+        assert_eq!(reader.fresh(), None);
+        assert_eq!(reader.latest(), Some([1, 2, 3]));
+
+        writer.write([4, 5, 6]);
+        writer.write([7, 8, 9]);
+        assert_eq!(reader.fresh(), Some([7, 8, 9]));
+        assert_eq!(reader.fresh(), None);
+    }
+
+    #[test]
+    fn sequence_wraparound() {
+        let mut latest = Latest::new();
+        latest.published = AtomicUsize::new(usize::MAX);
+        latest.started = AtomicUsize::new(usize::MAX);
+        let (mut writer, mut reader) = latest.split();
+
+        writer.write(1);
+        writer.write(2);
+        assert_eq!(reader.latest(), Some(2));
+        assert_eq!(reader.fresh(), Some(2));
+    }
+
+    #[test]
+    fn write_in_progress_across_wraparound() {
+        let mut latest = Latest::new();
+        latest.published = AtomicUsize::new(usize::MAX - 1);
+        latest.started = AtomicUsize::new(usize::MAX - 1);
+        let (mut writer, reader) = latest.split();
+
+        writer.write(1);
+        assert_eq!(reader.latest.published.load(Ordering::Relaxed), usize::MAX);
+
+        // Simulate a reader preempting the next write, which goes to the other slot.
+        reader.latest.started.store(2, Ordering::Relaxed);
+        assert_eq!(reader.latest(), Some(1));
+    }
+}
@@ -62,11 +62,30 @@
 #![warn(missing_debug_implementations, rust_2018_idioms)]
 #![cfg_attr(not(test), no_std)]
 
+mod adapters;
+mod bus;
+mod latest;
+#[cfg(feature = "mock")]
+pub mod mock;
+mod queue;
 mod readme;
+mod set;
+
+pub use adapters::{coalesce, coalesce_interval, debounce, guarded};
+pub use bus::{EventBus, Publisher, Subscriber};
+pub use latest::{Latest, LatestReader, LatestWriter};
+pub use queue::{ClassConsumer, ClassProducer, ClassProducers, ClassQueue};
+pub use set::{InterruptSet, InterruptSetIter};
 
 use core::fmt;
+#[cfg(feature = "multi-core")]
+use core::hint;
 use core::marker::PhantomData;
-use core::sync::atomic::{AtomicUsize, Ordering};
+use core::mem;
+use core::pin::Pin;
+#[cfg(feature = "multi-core")]
+use core::sync::atomic;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 
 /// Hooks interrupts and makes them available to the [`scope`] API.
 ///
@@ -74,6 +93,15 @@ use core::sync::atomic::{AtomicUsize, Ordering};
 /// This is generally architecture- or even MCU-specific. On Cortex-M devices, this should usually
 /// be the `#[interrupt]` macro exported by the device-specific PAC.
 ///
+/// When an interrupt fires while no handler is registered for it, the generated code panics if
+/// debug assertions are enabled, and goes into an infinite loop otherwise. With the `panic-never`
+/// Cargo feature, it always goes into an infinite loop, so that no panicking code is generated.
+/// This can be configured per invocation, see [Unregistered interrupts](#unregistered-interrupts).
+///
+/// When the `names` Cargo feature is enabled (the default), the generated enum also gets a `name`
+/// method returning the name of the interrupt variant, as well as a `Display` implementation
+/// printing it. Disabling the feature removes the string table from the binary.
+///
 /// It is not necessary to hook *all* interrupts. Only those that should be made available to the
 /// [`scope`] API are required. Since every hooked interrupt comes with a cost in code and data
 /// size, it is advisable to only hook the interrupts needed by the application.
@@ -98,389 +126,3249 @@ use core::sync::atomic::{AtomicUsize, Ordering};
 /// # fn main() {}  // macro must be called outside a function
 /// ```
 ///
+/// The hook attribute can be any attribute that is valid on a function. In particular, it can be
+/// referenced by its path, and it can take arguments:
+///
+/// ```
+/// use irq::scoped_interrupts;
+/// // `cortex-m-rt`'s `#[interrupt]` expects the PAC's `interrupt` enum to be in scope.
+/// use mock_pac::interrupt;
+///
+/// scoped_interrupts! {
+///     enum Interrupt {
+///         INT0,
+///     }
+///
+///     use #[mock_pac::interrupt];
+/// }
+///
+/// mod other {
+///     irq::scoped_interrupts! {
+///         enum Interrupt {
+///             TIMER0,
+///         }
+///
+///         use #[cfg_attr(not(target_os = "none"), inline(never))];
+///     }
+/// }
+///
+/// # fn main() {}
+/// ```
+///
+/// Several hook attributes can be given, which are all applied to every veneer. This can be used
+/// to eg. place the veneers in RAM:
+///
+/// ```
+/// use irq::scoped_interrupts;
+/// use mock_pac::interrupt;
+///
+/// scoped_interrupts! {
+///     enum Interrupt {
+///         INT0,
+///     }
+///
+///     use #[interrupt] #[cfg_attr(target_os = "none", link_section = ".ramfunc")];
+/// }
+///
+/// # fn main() {}
+/// ```
+///
 /// Also refer to `examples/mock-pac.rs` for a standalone version with more comments.
 ///
-/// [svd2rust]: https://github.com/rust-embedded/svd2rust
-/// [`scope`]: fn.scope.html
-#[macro_export]
-macro_rules! scoped_interrupts {
-    (
-        $( #[$enum_attr:meta] )*
-        $v:vis enum $name:ident {
-            $(
-                $interrupt:ident
-            ),+
-
-            $(,)?
-        }
-
-        use #[$hook_attr:meta];
-    ) => {
-        // Step 1: Declare an Actual Enum like that.
-        $( #[$enum_attr] )*
-        $v enum $name {
-            $(
-                $interrupt,
-            )+
-        }
-
-        // Step 2: Hook all the interrupts and put veneers in place.
-
-        // Extra module needed to avoid name collisions.
-        pub(crate) mod statics {
-            $(
-                #[allow(bad_style)]
-                pub(crate) static $interrupt: $crate::HandlerAddr = $crate::HandlerAddr::new();
-            )+
-        }
-
-        // Now register veneers using the provided hook attribute.
-        $(
-            #[$hook_attr]
-            #[allow(bad_style, dead_code)]
-            unsafe fn $interrupt() {
-                let handler = self::statics::$interrupt.load();
-                if handler == 0 {
-                    if cfg!(debug_assertions) {
-                        // Pay for a panic call when debug assertions are on. It is a bug when this
-                        // code is hit, so tell the user.
-                        panic!(concat!(
-                            "no handler registered for ",
-                            ::core::stringify!($interrupt)
-                        ));
-                    } else {
-                        // Without debug assertions, go into an infinite loop when no handler is
-                        // registered. This matches the behavior of cortex-m-rt's default handler.
-                        // We load from the static to defeat LLVM's loop optimizations to work
-                        // around https://github.com/rust-lang/rust/issues/28728.
-                        while self::statics::$interrupt.load() == 0 {}
-                    }
-                } else {
-                    let handler = handler as *mut $crate::Handler<'_>;
-
-                    // Soundness:
-                    // - Relies on the user-facing API to manage the handler lifetime (which is
-                    //   dangling here).
-                    // - Relies on interrupts not being reentrant
-                    (*handler).invoke();
-                }
-            }
-        )+
-
-        // Step 3: Implement the `Interrupt` trait.
-        // Safety: `deregister_all()` is correctly implemented by this macro.
-        unsafe impl $crate::Interrupt for $name {
-            unsafe fn register(self, handler: &mut $crate::Handler<'_>) {
-                match self {
-                    $(
-                        Self::$interrupt => {
-                            self::statics::$interrupt.store(handler as *mut _ as usize);
-                        }
-                    )+
-                }
-            }
-
-            fn deregister_all() {
-                // Safety: We store 0, which disables the interrupt, which is always safe.
-                unsafe {
-                    $(
-                        self::statics::$interrupt.store(0);
-                    )+
-                }
-            }
-        }
-    };
-}
-
-/// Defines a closure-based interrupt handler that can use stack-local data.
+/// Attributes and doc comments on the enum and on its variants are forwarded to the generated
+/// enum, so that it can be documented like any other enum. Variants can not be conditionally
+/// compiled with `#[cfg]`, though, since that would not remove them from the generated code:
 ///
-/// This is a convenience macro that creates a [`&mut Handler`][`Handler`] variable that can be
-/// passed to [`Scope::register`].
+/// ```
+/// use irq::scoped_interrupts;
+/// use mock_pac::interrupt;
 ///
-/// # Examples
+/// scoped_interrupts! {
+///     /// The interrupts used by the application.
+///     #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+///     pub enum Interrupt {
+///         /// UART0 RX.
+///         INT0,
+///         /// Replaced by `INT2`.
+///         #[deprecated]
+///         INT1,
+///         INT2,
+///     }
+///
+///     use #[interrupt];
+/// }
 ///
+/// # fn main() {}
 /// ```
-/// # use irq::handler;
-/// let mut i = 0;
-/// handler!(my_handler = || i += 1);
+///
+/// # Hooking interrupts of multiple devices
+///
+/// The hook attributes given by the `use` line apply to all interrupts. Individual interrupts can
+/// specify different hook attributes after their name, which replace the default ones. This allows
+/// hooking interrupts from several PACs (eg. a device and its radio co-processor) with one
+/// interrupt enum:
+///
 /// ```
+/// use irq::scoped_interrupts;
+/// use mock_pac::interrupt;
 ///
-/// [`Handler`]: struct.Handler.html
-/// [`Scope::register`]: struct.Scope.html#method.register
-#[macro_export]
-macro_rules! handler {
-    ($name:ident = $e:expr) => {
-        let mut closure = $e;
-        let $name = &mut $crate::Handler::new(&mut closure);
-    };
-}
-
-/// Creates a scope in which interrupt handlers using stack-local data can be registered.
+/// # mod radio_pac { pub use mock_pac::interrupt; }
+/// scoped_interrupts! {
+///     enum Interrupt {
+///         INT0,
+///         INT1 use #[radio_pac::interrupt],
+///     }
 ///
-/// When this function returns, all handlers will be deregistered again. Note that `scope` calls
-/// should not be nested, or all interrupt handlers will be deregistered as soon as the *inner* call
-/// returns.
-pub fn scope<'env, I, F, R>(f: F) -> R
-where
-    I: Interrupt,
-    F: FnOnce(&Scope<'env, I>) -> R,
-{
-    let scope = Scope { _p: PhantomData };
-
-    let result = f(&scope);
-
-    // Drop the scope, deregistering all interrupt handlers. This is required for soundness: Any
-    // handler passed to `scope` only lives as long as `'env`, which may end right after this
-    // function returns.
-    drop(scope);
-
-    result
-}
-
-/// An interrupt scope created by the [`scope`] function.
+///     use #[interrupt];
+/// }
 ///
-/// [`scope`]: fn.scope.html
-#[allow(missing_debug_implementations)]
-pub struct Scope<'env, I: Interrupt> {
-    // Make `'env` invariant
-    _p: PhantomData<(I, &'env mut &'env ())>,
-}
-
-impl<'env, I: Interrupt> Scope<'env, I> {
-    /// Registers an interrupt handler for the duration of this scope.
-    ///
-    /// Once the enclosing [`scope`] call returns, all interrupts that were registered using this
-    /// method will be deregistered again.
-    ///
-    /// # Parameters
-    ///
-    /// * `interrupt`: The interrupt to handle. This must be a variant of an enum generated by the
-    ///   [`scoped_interrupts!`] macro.
-    /// * `handler`: The handler closure to hook up to the interrupt. For convenience, this can be
-    ///   created using the [`handler!`] macro.
-    ///
-    /// [`scope`]: fn.scope.html
-    /// [`scoped_interrupts!`]: macro.scoped_interrupts.html
-    /// [`handler!`]: macro.handler.html
-    #[inline]
-    pub fn register(&self, interrupt: I, handler: &'env mut Handler<'env>) {
-        // `handler` must be taken by mutable reference here, or the same handler could be
-        // registered for 2 different interrupts.
-        // Its lifetime must also be forced to `'env` to indicate that it may flow into `self`.
-        unsafe {
-            interrupt.register(handler);
-        }
+/// # fn main() {}
+/// ```
+///
+/// # Renaming interrupts
+///
+/// The veneers have to be named like the interrupts they hook, which often results in
+/// SCREAMING_CASE variant names. A variant can instead be declared as an alias for the interrupt
+/// with `Variant => INTERRUPT`. Its veneer is then named after `INTERRUPT`, while the enum uses the
+/// variant name:
+///
+/// ```
+/// use irq::{handler, scope, scoped_interrupts};
+/// use mock_pac::interrupt;
+///
+/// scoped_interrupts! {
+///     enum Interrupt {
+///         Radio => INT0,
+///         Timer => INT1,
+///         INT2,
+///     }
+///
+///     use #[interrupt];
+/// }
+///
+/// fn main() {
+///     handler!(radio = || {});
+///
+///     scope(|scope| {
+///         scope.register(Interrupt::Radio, radio);
+///     });
+/// }
+/// ```
+///
+/// Aliases can be combined with a per-variant hook attribute, as in
+/// `Radio => INT1 use #[radio_pac::interrupt],`.
+///
+/// # Converting to the PAC's interrupt type
+///
+/// Unmasking interrupts, setting their priority or pending them requires the interrupt type of
+/// the PAC. When an `impl From for Type;` line is given before the `use` line, the generated enum
+/// can be converted to the PAC type `Type` using `From`/`Into`, and gets an `irq_number` method
+/// returning the interrupt number:
+///
+/// ```
+/// use irq::scoped_interrupts;
+/// use mock_pac::interrupt;
+///
+/// scoped_interrupts! {
+///     enum Interrupt {
+///         INT0,
+///         Timer => INT2,
+///     }
+///
+///     impl From for mock_pac::interrupt;
+///
+///     use #[interrupt];
+/// }
+///
+/// fn main() {
+///     let timer: interrupt = Interrupt::Timer.into();
+///     assert_eq!(timer, interrupt::INT2);
+///     assert_eq!(Interrupt::Timer.irq_number(), 2);
+/// }
+/// ```
+///
+/// # Unregistered interrupts
+///
+/// What happens when an interrupt fires while no handler is registered for it can be configured
+/// with an `unregistered => behavior;` line before the `use` line. The behavior is one of:
+///
+/// * `panic`: Always panic, even without debug assertions and with the `panic-never` feature.
+/// * `spin`: Go into an infinite loop, until a handler is registered.
+/// * `ignore`: Return from the veneer without doing anything.
+/// * `call(path)`: Call the function at `path`, which takes the interrupt enum as its only
+///   argument. This can be used to report the interrupt to a fault handler.
+///
+/// ```
+/// use irq::scoped_interrupts;
+/// use mock_pac::interrupt;
+///
+/// scoped_interrupts! {
+///     enum Interrupt {
+///         INT0,
+///         INT1,
+///     }
+///
+///     unregistered => call(report_spurious);
+///
+///     use #[interrupt];
+/// }
+///
+/// fn report_spurious(interrupt: Interrupt) {
+///     // Log the interrupt, and mask it so that it doesn't fire again...
+/// # let _ = interrupt;
+/// }
+///
+/// # fn main() {}
+/// ```
+///
+/// Note that the interrupt flag of the peripheral is not cleared by the veneer, so level-triggered
+/// interrupts will fire again immediately after the veneer returns when using `ignore` or `call`.
+///
+/// # Exceptions
+///
+/// Core exceptions like `SysTick`, `PendSV` and `SVCall` can be hooked just like device
+/// interrupts, by using `cortex-m-rt`'s `#[exception]` attribute. The variants must be named after
+/// the exceptions they hook:
+///
+/// ```
+/// use cortex_m_rt::exception;
+/// use irq::{handler, scope, scoped_interrupts};
+///
+/// scoped_interrupts! {
+///     enum Exception {
+///         SysTick,
+///         PendSV,
+///     }
+///
+///     use #[exception];
+/// }
+///
+/// fn main() {
+///     let mut ticks = 0;
+///     handler!(systick = || ticks += 1);
+///
+///     scope(|scope| {
+///         scope.register(Exception::SysTick, systick);
+///     });
+/// }
+/// ```
+///
+/// Exceptions can also be hooked from an enum of device interrupts, by giving them their own hook
+/// attribute (eg. `SysTick use #[exception],`). `HardFault` and `DefaultHandler` can not be hooked,
+/// since `cortex-m-rt` requires their handlers to have a different signature.
+///
+/// # Veneer ABI
+///
+/// The veneers are declared as Rust functions by default. When the vector table or a dispatcher
+/// written in C requires the handlers to use a specific ABI, it can be specified before the hook
+/// attribute:
+///
+/// ```
+/// use irq::scoped_interrupts;
+///
+/// scoped_interrupts! {
+///     enum Interrupt {
+///         INT0,
+///     }
+///
+///     use extern "C" #[no_mangle];
+/// }
+///
+/// # fn main() {}
+/// ```
+///
+/// # Hook macros
+///
+/// Some runtimes bind interrupt handlers with a macro like `interrupt!(NAME, handler)` instead of
+/// an attribute. Such a macro can be used with `use hook_macro!(NAME, FN);`, where `NAME` and `FN`
+/// are written as-is. The macro is then invoked once per interrupt, with the interrupt name and
+/// the path to its veneer:
+///
+/// ```
+/// use irq::scoped_interrupts;
+///
+/// // Binds `handler` to the interrupt `name`, like `cortex-m-rt` 0.5's `interrupt!` macro.
+/// macro_rules! bind_interrupt {
+///     ($name:ident, $handler:path) => {
+///         #[no_mangle]
+///         pub unsafe extern "C" fn $name() {
+///             let handler: fn() = $handler;
+///             handler();
+///         }
+///     };
+/// }
+///
+/// scoped_interrupts! {
+///     enum Interrupt {
+///         UART0,
+///         TIMER0,
+///     }
+///
+///     use bind_interrupt!(NAME, FN);
+/// }
+///
+/// # fn main() {}
+/// ```
+///
+/// The veneers are declared in modules named after the interrupts, so the hook macro is free to
+/// declare items named after them, too. Per-variant hook attributes are not supported in this
+/// form.
+///
+/// The veneer passed to the hook macro is a safe `fn()`, since that is what such macros expect.
+/// Invoking it while the handler is already running (eg. from within the handler) would create
+/// aliasing mutable references to the handler's state, so the hook macro must not make it callable
+/// from safe code: Any function it declares to wrap the veneer has to be an `unsafe fn`, like
+/// the veneers generated for hook attributes.
+///
+/// # Without `cortex-m-rt`
+///
+/// The hook attribute does not have to come from `cortex-m-rt` or a PAC. Projects with their own
+/// startup code and vector table (eg. bootloaders) can give the veneers a C ABI and reference them
+/// from the vector table directly. The veneers are declared in the module containing the
+/// `scoped_interrupts!` invocation, under the name of their interrupt:
+///
+/// ```
+/// use irq::scoped_interrupts;
+///
+/// scoped_interrupts! {
+///     pub enum Interrupt {
+///         UART0,
+///         TIMER0,
+///     }
+///
+///     use extern "C" #[inline(never)];
+/// }
+///
+/// /// The device-specific part of the vector table, in the order mandated by the hardware.
+/// #[cfg_attr(target_os = "none", link_section = ".vector_table.interrupts")]
+/// #[used]
+/// static INTERRUPTS: [unsafe extern "C" fn(); 2] = [UART0, TIMER0];
+///
+/// # fn main() {}
+/// ```
+///
+/// Alternatively, `use extern "C" #[no_mangle];` exports the veneers under their own name, so
+/// that they can be referenced from a vector table written in assembly or C.
+///
+/// # Virtual interrupts
+///
+/// When the `use` line is omitted, no veneers are generated. The interrupts are then *virtual*:
+/// They do not correspond to a vector in the vector table, but handlers can still be registered
+/// for them in a [`scope`]. Calling [`Interrupt::dispatch`] invokes the registered handler.
+///
+/// This allows exposing the interrupt sources of a secondary interrupt controller (eg. a GPIO
+/// expander or an FPGA interrupt aggregator) just like hardware interrupts. The handler of the
+/// parent interrupt demultiplexes the sources and dispatches them:
+///
+/// ```
+/// use irq::{handler, scope, scoped_interrupts, Interrupt as _};
+/// use mock_pac::interrupt;
+///
+/// scoped_interrupts! {
+///     enum Interrupt {
+///         INT0,
+///     }
+///
+///     use #[interrupt];
+/// }
+///
+/// scoped_interrupts! {
+///     // No `use` line, so these are virtual.
+///     enum Pin {
+///         BUTTON,
+///         CARD_DETECT,
+///     }
+/// }
+///
+/// # fn read_expander_flags() -> u8 { 0 }
+/// fn main() {
+///     handler!(expander = || {
+///         let flags = read_expander_flags();
+///         if flags & 0b01 != 0 {
+///             // Safety: Only this handler dispatches `Pin` interrupts.
+///             unsafe { Pin::BUTTON.dispatch() }
+///         }
+///         if flags & 0b10 != 0 {
+///             unsafe { Pin::CARD_DETECT.dispatch() }
+///         }
+///     });
+///     handler!(button = || {});
+///     handler!(card_detect = || {});
+///
+///     scope(|pins| {
+///         pins.register(Pin::BUTTON, button);
+///         pins.register(Pin::CARD_DETECT, card_detect);
+///
+///         scope(|scope| {
+///             scope.register(Interrupt::INT0, expander);
+///         });
+///     });
+/// }
+/// ```
+///
+/// Like hooked interrupts, dispatching a virtual interrupt without a registered handler panics
+/// when debug assertions are enabled. This can be configured with an `unregistered => behavior;`
+/// line after the enum, as described in [Unregistered interrupts](#unregistered-interrupts). A
+/// virtual interrupt without handler can then be ignored instead, for example:
+///
+/// ```
+/// use irq::scoped_interrupts;
+///
+/// scoped_interrupts! {
+///     enum Pin {
+///         BUTTON,
+///         CARD_DETECT,
+///     }
+///
+///     unregistered => ignore;
+/// }
+///
+/// # fn main() {}
+/// ```
+///
+/// # Subsets
+///
+/// Exiting a [`scope`] deregisters the handlers of *all* interrupts of the enum it was used with.
+/// To allow independent parts of an application (eg. a radio driver and a motor driver) to each
+/// use their own scope at the same time, the hooked interrupts can be partitioned into `subset`s.
+/// Every subset is an enum of its own that can be used with [`scope`]. Its variants refer to the
+/// same hooked interrupts as the ones in the full enum, but a scope using the subset will only
+/// deregister the interrupts in that subset.
+///
+/// ```
+/// use irq::{handler, scope, scoped_interrupts};
+/// use mock_pac::interrupt;
+///
+/// scoped_interrupts! {
+///     enum Interrupt {
+///         INT0,
+///         INT1,
+///         INT2,
+///     }
+///
+///     use #[interrupt];
+///
+///     subset RadioInterrupt {
+///         INT0,
+///         INT1,
+///     }
+///
+///     pub subset MotorInterrupt {
+///         INT2,
+///     }
+/// }
+///
+/// fn main() {
+///     handler!(radio = || {});
+///     handler!(motor = || {});
+///
+///     scope(|radio_scope| {
+///         radio_scope.register(RadioInterrupt::INT0, radio);
+///
+///         scope(|motor_scope| {
+///             motor_scope.register(MotorInterrupt::INT2, motor);
+///         });
+///
+///         // The radio handler is still registered here.
+///     });
+/// }
+/// ```
+///
+/// Subsets should not overlap while their scopes are active at the same time, since either scope
+/// would remove the handler registered by the other one when it ends.
+///
+/// # Groups
+///
+/// Related interrupts that should be paused and resumed together (eg. all interrupts of a
+/// subsystem that can be powered down at runtime) can be declared as a `group`. A group is a
+/// constant [`InterruptSet`] of the full enum that can be passed to [`Scope::pause_set`] and
+/// [`Scope::resume_set`]:
+///
+/// ```
+/// use irq::{handler, scope, scoped_interrupts};
+/// use mock_pac::interrupt;
+///
+/// scoped_interrupts! {
+///     enum Interrupt {
+///         INT0,
+///         INT1,
+///         INT2,
+///     }
+///
+///     use #[interrupt];
+///
+///     group RADIO {
+///         INT0,
+///         INT1,
+///     }
+/// }
+///
+/// fn main() {
+///     handler!(radio = || {});
+///     handler!(timer = || {});
+///
+///     scope(|scope| {
+///         scope.register(Interrupt::INT0, radio);
+///         scope.register(Interrupt::INT1, timer);
+///
+///         // Power down the radio.
+///         scope.pause_set(RADIO);
+///         // ...
+///         scope.resume_set(RADIO);
+///     });
+/// }
+/// ```
+///
+/// Subsets and groups can be declared in any order after the `use` line.
+///
+/// A module can contain any number of `scoped_interrupts!` invocations, as long as their enums
+/// and veneers have distinct names.
+///
+/// [svd2rust]: https://github.com/rust-embedded/svd2rust
+/// [`scope`]: fn.scope.html
+/// [`InterruptSet`]: struct.InterruptSet.html
+/// [`Scope::pause_set`]: struct.Scope.html#method.pause_set
+/// [`Scope::resume_set`]: struct.Scope.html#method.resume_set
+/// [`Interrupt::dispatch`]: trait.Interrupt.html#tymethod.dispatch
+#[macro_export]
+macro_rules! scoped_interrupts {
+    // Declares a subset enum, which shares the veneers of the full enum.
+    (@subset $main:ident, $( #[$attr:meta] )* $v:vis $name:ident { $( $interrupt:ident ),+ }) => {
+        $( #[$attr] )*
+        $v enum $name {
+            $(
+                $interrupt,
+            )+
+        }
+
+        $crate::scoped_interrupts!(@impl $main, $v $name { $( $interrupt ),+ });
+    };
+
+    // Declares a group as a constant set of interrupts of the full enum.
+    (@group $main:ident, $( #[$attr:meta] )* $v:vis $name:ident { $( $interrupt:ident ),+ }) => {
+        $( #[$attr] )*
+        #[allow(non_upper_case_globals, dead_code, deprecated)]
+        $v const $name: $crate::InterruptSet<$main> =
+            $crate::InterruptSet::__from_indices(&[$( $main::$interrupt as usize ),+]);
+    };
+
+    // Rejects `#[cfg]` on variants. It would only remove the variant from the enum, but not from
+    // the code generated for every variant. `$attr` is `[...]`, the contents of one attribute.
+    (@check_variant_attrs) => {};
+
+    (@check_variant_attrs [cfg $( $cfg:tt )*] $( $rest:tt )*) => {
+        ::core::compile_error!("`#[cfg]` is not supported on variants in `scoped_interrupts!`");
+    };
+
+    (@check_variant_attrs [$( $attr:tt )*] $( $rest:tt )*) => {
+        $crate::scoped_interrupts!(@check_variant_attrs $( $rest )*);
+    };
+
+    // Declares the veneers for all `$interrupt`s. `$qualifier` is `[]` or `[extern "ABI"]`.
+    // `$hook` is `[#[attr]...]`, the hook attributes applied to all veneers by default.
+    // `$variant_hook` is `[]`, or `[#[attr]...]` if the variant overrides the hook attributes.
+    // `$symbol` is `[]`, or `[IRQ]` if the variant is an alias for the interrupt named `IRQ`.
+    (@veneers $qualifier:tt $hook:tt $name:ident {
+        $( $interrupt:ident $symbol:tt $variant_hook:tt ),+
+    }) => {
+        $(
+            $crate::scoped_interrupts!(
+                @veneer $qualifier $variant_hook $symbol $hook $name $interrupt
+            );
+        )+
+    };
+
+    // Uses the default hook attributes for variants that don't override them.
+    (@veneer $qualifier:tt [] $symbol:tt $hook:tt $name:ident $interrupt:ident) => {
+        $crate::scoped_interrupts!(@veneer $qualifier $hook $symbol $hook $name $interrupt);
+    };
+
+    // Names the veneer after the variant, unless the variant is an alias.
+    (@veneer $qualifier:tt $variant_hook:tt [] $hook:tt $name:ident $interrupt:ident) => {
+        $crate::scoped_interrupts!(
+            @veneer $qualifier $variant_hook [$interrupt] $hook $name $interrupt
+        );
+    };
+
+    // Declares the veneer for `$interrupt`, which invokes the registered handler.
+    (@veneer [$( $qualifier:tt )*] [$( #[$hook_attr:meta] )+] [$symbol:ident] $default_hook:tt
+        $name:ident $interrupt:ident
+    ) => {
+        $( #[$hook_attr] )+
+        #[allow(bad_style, dead_code, deprecated)]
+        unsafe $( $qualifier )* fn $symbol() {
+            $crate::scoped_interrupts!(@dispatch $name $interrupt);
+        }
+    };
+
+    // Declares the veneers for all `$interrupt`s and hooks them by invoking the `$hook` macro.
+    (@hook_macros $hook:tt $name:ident { $( $interrupt:ident $symbol:tt ),+ }) => {
+        $(
+            $crate::scoped_interrupts!(@hook_macro $hook $name $interrupt $symbol);
+        )+
+    };
+
+    // Names the veneer module after the variant, unless the variant is an alias.
+    (@hook_macro $hook:tt $name:ident $interrupt:ident []) => {
+        $crate::scoped_interrupts!(@hook_macro $hook $name $interrupt [$interrupt]);
+    };
+
+    // Hook macros typically declare a function named after the interrupt, so the veneer is put in
+    // a module of the same name, which lives in a different namespace.
+    (@hook_macro [$( $hook:ident )::+] $name:ident $interrupt:ident [$symbol:ident]) => {
+        #[allow(bad_style)]
+        mod $symbol {
+            use super::*;
+
+            #[allow(dead_code, deprecated)]
+            fn veneer() {
+                unsafe {
+                    $crate::scoped_interrupts!(@dispatch $name $interrupt);
+                }
+            }
+
+            $( $hook )::+!($symbol, veneer);
+        }
+    };
+
+    // Declares how to handle interrupts firing while no handler is registered. `$behavior` is `[]`
+    // or the behavior given by the `unregistered` line.
+    (@unregistered_impl $behavior:tt $name:ident { $( $interrupt:ident ),+ }) => {
+        #[allow(deprecated)]
+        impl $name {
+            /// Invokes the handler registered for `self`, or handles `self` firing while no
+            /// handler is registered.
+            ///
+            /// This is shared by all veneers of the enum, which only call it, so that none of this
+            /// code is duplicated per interrupt.
+            #[inline(never)]
+            unsafe fn __dispatch(self) {
+                let slot = self.__handler_addr();
+                if !slot.dispatch() {
+                    self.__unregistered(slot);
+                }
+            }
+
+            /// Handles `self` firing while no handler is registered in `slot`.
+            #[inline(always)]
+            #[allow(unused_variables)]
+            fn __unregistered(self, slot: &'static $crate::HandlerAddr) {
+                $crate::scoped_interrupts!(@unregistered $behavior self slot);
+            }
+
+            /// Returns the name of `self`, for reporting it when no handler is registered.
+            #[allow(dead_code)]
+            fn __name(&self) -> &'static str {
+                match self {
+                    $(
+                        Self::$interrupt => ::core::stringify!($interrupt),
+                    )+
+                }
+            }
+        }
+    };
+
+    // Handles the interrupt `$interrupt` firing while no handler is registered in `$slot`.
+    (@unregistered [] $interrupt:ident $slot:ident) => {
+        $crate::__unregistered!($interrupt.__name(), $slot);
+    };
+
+    (@unregistered [panic] $interrupt:ident $slot:ident) => {
+        panic!("no handler registered for {}", $interrupt.__name());
+    };
+
+    (@unregistered [spin] $interrupt:ident $slot:ident) => {
+        // Load from the slot so that the loop has a side effect, see `__unregistered!`.
+        while $slot.load() == 0 {}
+    };
+
+    (@unregistered [ignore] $interrupt:ident $slot:ident) => {};
+
+    (@unregistered [call($fault_handler:path)] $interrupt:ident $slot:ident) => {
+        $fault_handler($interrupt);
+    };
+
+    // Invokes the handler registered for `$interrupt`. This is the body of its veneer.
+    (@dispatch $name:ident $interrupt:ident) => {
+        // The work is done by a function shared by all veneers of `$name`, to keep the veneers
+        // small.
+        $name::$interrupt.__dispatch();
+    };
+
+    // No PAC interrupt type was given, so there is nothing to convert to.
+    (@from $v:vis $name:ident [] { $( $interrupt:ident $symbol:tt ),+ }) => {};
+
+    // Implements the conversion of `$name` to the PAC's interrupt type `$pac`.
+    (@from $v:vis $name:ident [$pac:ty] { $( $interrupt:ident $symbol:tt ),+ }) => {
+        #[allow(deprecated)]
+        impl ::core::convert::From<$name> for $pac {
+            fn from(interrupt: $name) -> Self {
+                match interrupt {
+                    $(
+                        $name::$interrupt => {
+                            $crate::scoped_interrupts!(@pac_variant $pac, $interrupt $symbol)
+                        }
+                    )+
+                }
+            }
+        }
+
+        #[allow(deprecated)]
+        impl $name {
+            /// Returns the number of the hooked interrupt, as assigned by the PAC.
+            #[allow(dead_code)]
+            $v fn irq_number(&self) -> u16 {
+                match self {
+                    $(
+                        Self::$interrupt => {
+                            let interrupt =
+                                $crate::scoped_interrupts!(@pac_variant $pac, $interrupt $symbol);
+                            interrupt as u16
+                        }
+                    )+
+                }
+            }
+        }
+    };
+
+    // Names the variant of the PAC's interrupt type `$pac` that is hooked by `$interrupt`.
+    (@pac_variant $pac:ty, $interrupt:ident []) => {
+        $crate::scoped_interrupts!(@pac_variant $pac, $interrupt [$interrupt])
+    };
+
+    (@pac_variant $pac:ty, $interrupt:ident [$symbol:ident]) => {{
+        type Pac = $pac;
+        Pac::$symbol
+    }};
+
+    // Implements `Interrupt` for `$name`, whose variants are a subset of `$main`'s variants.
+    (@impl $main:ident, $v:vis $name:ident { $( $interrupt:ident ),+ }) => {
+        // Safety: `deregister_all()` is correctly implemented by this macro.
+        #[allow(deprecated)]
+        unsafe impl $crate::Interrupt for $name {
+            unsafe fn register(self, handler: &$crate::Handler<'_>) {
+                match self {
+                    $(
+                        Self::$interrupt => {
+                            $main::$interrupt.__handler_addr().store(handler as *const _ as usize);
+                        }
+                    )+
+                }
+            }
+
+            fn deregister_all() {
+                // Safety: We store 0, which disables the interrupt, which is always safe.
+                unsafe {
+                    $(
+                        $main::$interrupt.__handler_addr().store(0);
+                        $main::$interrupt.__handler_addr().set_paused(false);
+                    )+
+                }
+
+                // Handlers that are still running on another core might still use their closure.
+                $(
+                    $main::$interrupt.__handler_addr().wait_idle();
+                )+
+            }
+
+            fn set_paused(&self, paused: bool) {
+                match self {
+                    $(
+                        Self::$interrupt => $main::$interrupt.__handler_addr().set_paused(paused),
+                    )+
+                }
+            }
+
+            fn is_paused(&self) -> bool {
+                match self {
+                    $(
+                        Self::$interrupt => $main::$interrupt.__handler_addr().is_paused(),
+                    )+
+                }
+            }
+
+            $crate::__if_single_core! {
+                fn current() -> Option<Self> {
+                    // Subsets share the slots of the full interrupt enum.
+                    $(
+                        if $main::$interrupt.__handler_addr().is_active() {
+                            return Some(Self::$interrupt);
+                        }
+                    )+
+                    None
+                }
+            }
+
+            const COUNT: usize = [$( ::core::stringify!($interrupt) ),+].len();
+
+            fn index(&self) -> usize {
+                match self {
+                    $(
+                        Self::$interrupt => Self::$interrupt as usize,
+                    )+
+                }
+            }
+
+            fn from_index(index: usize) -> Option<Self> {
+                $(
+                    if index == Self::$interrupt as usize {
+                        return Some(Self::$interrupt);
+                    }
+                )+
+                None
+            }
+
+            unsafe fn dispatch(&self) {
+                match self {
+                    $(
+                        Self::$interrupt => {
+                            $crate::scoped_interrupts!(@dispatch $main $interrupt);
+                        }
+                    )+
+                }
+            }
+        }
+
+        // Emit interrupt names, unless disabled to save space.
+        $crate::__if_names! {
+            #[allow(deprecated)]
+            impl $name {
+                /// Returns the name of this interrupt, as declared in `scoped_interrupts!`.
+                #[allow(dead_code)]
+                $v fn name(&self) -> &'static str {
+                    match self {
+                        $(
+                            Self::$interrupt => ::core::stringify!($interrupt),
+                        )+
+                    }
+                }
+            }
+
+            impl ::core::fmt::Display for $name {
+                fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                    f.write_str(self.name())
+                }
+            }
+        }
+    };
+
+    (
+        $( #[$enum_attr:meta] )*
+        $v:vis enum $name:ident {
+            $(
+                $( #[$( $variant_attr:tt )*] )*
+                $interrupt:ident $( => $symbol:ident )? $( use $( #[$variant_hook:meta] )+ )?
+            ),+
+
+            $(,)?
+        }
+
+        $( impl From for $pac:ty; )?
+
+        $( unregistered => $behavior:ident $( ($fault_handler:path) )?; )?
+
+        use $( extern $abi:literal )? $( #[$hook_attr:meta] )+;
+
+        $(
+            $( #[$item_attr:meta] )*
+            $item_v:vis $kind:ident $item:ident {
+                $(
+                    $item_interrupt:ident
+                ),+
+
+                $(,)?
+            }
+        )*
+    ) => {
+        // Step 1: Declare an Actual Enum like that.
+        $(
+            $crate::scoped_interrupts!(@check_variant_attrs $( [$( $variant_attr )*] )*);
+        )+
+
+        $( #[$enum_attr] )*
+        $v enum $name {
+            $(
+                $( #[$( $variant_attr )*] )*
+                $interrupt,
+            )+
+        }
+
+        // Step 2: Hook all the interrupts and put veneers in place.
+
+        // The statics are declared inside associated functions of the enum, so that several enums
+        // can be declared in the same module without their statics colliding.
+        #[allow(deprecated)]
+        impl $name {
+            /// Returns the slot holding the address of the handler registered for `self`.
+            #[inline(always)]
+            fn __handler_addr(&self) -> &'static $crate::HandlerAddr {
+                match self {
+                    $(
+                        Self::$interrupt => {
+                            static ADDR: $crate::HandlerAddr = $crate::HandlerAddr::new();
+                            &ADDR
+                        }
+                    )+
+                }
+            }
+        }
+
+        $crate::scoped_interrupts!(
+            @unregistered_impl [$( $behavior $( ($fault_handler) )? )?] $name { $( $interrupt ),+ }
+        );
+
+        // Now register veneers using the provided hook attribute.
+        $crate::scoped_interrupts!(
+            @veneers [$( extern $abi )?] [$( #[$hook_attr] )+] $name {
+                $( $interrupt [$( $symbol )?] [$( $( #[$variant_hook] )+ )?] ),+
+            }
+        );
+
+        // Step 3: Implement the `Interrupt` trait, and the conversion to the PAC's interrupt type.
+        $crate::scoped_interrupts!(@impl $name, $v $name { $( $interrupt ),+ });
+        $crate::scoped_interrupts!(
+            @from $v $name [$( $pac )?] { $( $interrupt [$( $symbol )?] ),+ }
+        );
+
+        // Step 4: Declare subsets and groups.
+        $(
+            $crate::scoped_interrupts!(
+                @$kind $name, $( #[$item_attr] )* $item_v $item { $( $item_interrupt ),+ }
+            );
+        )*
+    };
+
+    // Interrupts hooked by invoking a macro instead of applying an attribute.
+    (
+        $( #[$enum_attr:meta] )*
+        $v:vis enum $name:ident {
+            $(
+                $( #[$( $variant_attr:tt )*] )*
+                $interrupt:ident $( => $symbol:ident )?
+            ),+
+
+            $(,)?
+        }
+
+        $( impl From for $pac:ty; )?
+
+        $( unregistered => $behavior:ident $( ($fault_handler:path) )?; )?
+
+        use $( $hook:ident )::+ !(NAME, FN);
+
+        $(
+            $( #[$item_attr:meta] )*
+            $item_v:vis $kind:ident $item:ident {
+                $(
+                    $item_interrupt:ident
+                ),+
+
+                $(,)?
+            }
+        )*
+    ) => {
+        // Declare everything but the veneers, like for virtual interrupts.
+        $crate::scoped_interrupts! {
+            $( #[$enum_attr] )*
+            $v enum $name {
+                $( $( #[$( $variant_attr )*] )* $interrupt $( => $symbol )? ),+
+            }
+
+            $( impl From for $pac; )?
+
+            $( unregistered => $behavior $( ($fault_handler) )?; )?
+
+            use #[cfg(any())];
+
+            $(
+                $( #[$item_attr] )*
+                $item_v $kind $item {
+                    $( $item_interrupt ),+
+                }
+            )*
+        }
+
+        $crate::scoped_interrupts!(
+            @hook_macros [$( $hook )::+] $name { $( $interrupt [$( $symbol )?] ),+ }
+        );
+    };
+
+    // Virtual interrupts with a configured behavior for unregistered interrupts. This is a rule of
+    // its own, since an optional `unregistered` line would be ambiguous with the items following
+    // it.
+    (
+        $( #[$enum_attr:meta] )*
+        $v:vis enum $name:ident {
+            $(
+                $( #[$( $variant_attr:tt )*] )*
+                $interrupt:ident
+            ),+
+
+            $(,)?
+        }
+
+        unregistered => $behavior:ident $( ($fault_handler:path) )?;
+
+        $(
+            $( #[$item_attr:meta] )*
+            $item_v:vis $kind:ident $item:ident {
+                $(
+                    $item_interrupt:ident
+                ),+
+
+                $(,)?
+            }
+        )*
+    ) => {
+        $crate::scoped_interrupts! {
+            $( #[$enum_attr] )*
+            $v enum $name {
+                $( $( #[$( $variant_attr )*] )* $interrupt ),+
+            }
+
+            unregistered => $behavior $( ($fault_handler) )?;
+
+            use #[cfg(any())];
+
+            $(
+                $( #[$item_attr] )*
+                $item_v $kind $item {
+                    $( $item_interrupt ),+
+                }
+            )*
+        }
+    };
+
+    // Virtual interrupts, which are dispatched manually instead of being hooked.
+    (
+        $( #[$enum_attr:meta] )*
+        $v:vis enum $name:ident {
+            $(
+                $( #[$( $variant_attr:tt )*] )*
+                $interrupt:ident
+            ),+
+
+            $(,)?
+        }
+
+        $(
+            $( #[$item_attr:meta] )*
+            $item_v:vis $kind:ident $item:ident {
+                $(
+                    $item_interrupt:ident
+                ),+
+
+                $(,)?
+            }
+        )*
+    ) => {
+        // `cfg(any())` is never true, so this removes the veneers.
+        $crate::scoped_interrupts! {
+            $( #[$enum_attr] )*
+            $v enum $name {
+                $( $( #[$( $variant_attr )*] )* $interrupt ),+
+            }
+
+            use #[cfg(any())];
+
+            $(
+                $( #[$item_attr] )*
+                $item_v $kind $item {
+                    $( $item_interrupt ),+
+                }
+            )*
+        }
+    };
+}
+
+/// Private API for use by the `scoped_interrupts!` macro. Do not use.
+///
+/// Handles an interrupt that fired without a registered handler.
+#[cfg(not(feature = "panic-never"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __unregistered {
+    ($name:expr, $slot:expr) => {
+        if cfg!(debug_assertions) {
+            // Pay for a panic call when debug assertions are on. It is a bug when this code is
+            // hit, so tell the user.
+            panic!("no handler registered for {}", $name);
+        } else {
+            // Without debug assertions, go into an infinite loop when no handler is registered.
+            // This matches the behavior of cortex-m-rt's default handler.
+            // We load from the static to defeat LLVM's loop optimizations to work around
+            // https://github.com/rust-lang/rust/issues/28728.
+            while $slot.load() == 0 {}
+        }
+    };
+}
+
+/// Private API for use by the `scoped_interrupts!` macro. Do not use.
+///
+/// Handles an interrupt that fired without a registered handler.
+#[cfg(feature = "panic-never")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __unregistered {
+    ($name:expr, $slot:expr) => {
+        // The `panic-never` feature removes the panic even in debug builds, so just spin like in
+        // release builds.
+        while $slot.load() == 0 {}
+    };
+}
+
+/// Private API for use by the `scoped_interrupts!` macro. Do not use.
+///
+/// Expands to its input when the `names` feature is enabled.
+#[cfg(feature = "names")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __if_names {
+    ($($i:item)*) => {
+        $($i)*
+    };
+}
+
+/// Private API for use by the `scoped_interrupts!` macro. Do not use.
+///
+/// Expands to its input when the `names` feature is enabled.
+#[cfg(not(feature = "names"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __if_names {
+    ($($i:item)*) => {};
+}
+
+/// Private API for use by the `scoped_interrupts!` macro. Do not use.
+///
+/// Expands to its input when the `multi-core` feature is disabled.
+#[cfg(not(feature = "multi-core"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __if_single_core {
+    ($($t:tt)*) => {
+        $($t)*
+    };
+}
+
+/// Private API for use by the `scoped_interrupts!` macro. Do not use.
+///
+/// Expands to nothing when the `multi-core` feature is enabled.
+#[cfg(feature = "multi-core")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __if_single_core {
+    ($($t:tt)*) => {};
+}
+
+/// Defines a closure-based interrupt handler that can use stack-local data.
+///
+/// This is a convenience macro that creates a [`&mut Handler`][`Handler`] variable that can be
+/// passed to [`Scope::register`].
+///
+/// # Examples
+///
+/// ```
+/// # use irq::handler;
+/// let mut i = 0;
+/// handler!(my_handler = || i += 1);
+/// ```
+///
+/// # Scratch buffers
+///
+/// Since the handler can borrow anything that outlives the [`scope`], large temporary buffers do
+/// not have to be allocated on the interrupt stack on every invocation. Instead, the caller can
+/// provide a scratch buffer that the handler borrows mutably. The handler only captures a
+/// reference to it, and the buffer is available to the caller again once the scope has ended:
+///
+/// ```
+/// use irq::{handler, scope, scoped_interrupts};
+/// use mock_pac::interrupt;
+///
+/// scoped_interrupts! {
+///     enum Interrupt {
+///         INT0,
+///     }
+///
+///     use #[interrupt];
+/// }
+///
+/// # fn decode_packet(_scratch: &mut [u8]) {}
+/// fn main() {
+///     let mut scratch = [0u8; 1024];
+///     let scratch = &mut scratch[..];
+///
+///     handler!(radio = || decode_packet(scratch));
+///
+///     scope(|scope| {
+///         scope.register(Interrupt::INT0, radio);
+///     });
+/// }
+/// ```
+///
+/// [`Handler`]: struct.Handler.html
+/// [`Scope::register`]: struct.Scope.html#method.register
+/// [`scope`]: fn.scope.html
+#[macro_export]
+macro_rules! handler {
+    ($name:ident = $e:expr) => {
+        let mut closure = $e;
+        let $name = &mut $crate::Handler::new(&mut closure);
+    };
+}
+
+/// Asserts that the calling code runs in the handler of a specific hooked interrupt.
+///
+/// This can be used by drivers to encode that a function may only be called from the handler of
+/// a specific interrupt. Like [`debug_assert!`], the check is only performed when debug assertions
+/// are enabled. It panics if the interrupt currently being handled (as reported by
+/// [`current_interrupt`]) is not the given one.
+///
+/// Since [`current_interrupt`] can not tell which interrupt is running when the `multi-core`
+/// feature is enabled, this does nothing in that case.
+///
+/// # Examples
+///
+/// ```
+/// use irq::{assert_in_interrupt, scoped_interrupts};
+/// use mock_pac::interrupt;
+///
+/// scoped_interrupts! {
+///     enum Interrupt {
+///         INT0,
+///         INT1,
+///     }
+///
+///     use #[interrupt];
+/// }
+///
+/// /// Drains the receive FIFO. Must only be called from the `INT0` handler.
+/// fn drain_rx_fifo() {
+///     assert_in_interrupt!(Interrupt::INT0);
+///
+///     // ...
+/// }
+/// # fn main() {}
+/// ```
+///
+/// [`debug_assert!`]: https://doc.rust-lang.org/core/macro.debug_assert.html
+/// [`current_interrupt`]: fn.current_interrupt.html
+#[macro_export]
+macro_rules! assert_in_interrupt {
+    ($interrupt:path) => {{
+        // Nothing is tracked with the `multi-core` feature, so there is nothing to check.
+        $crate::__if_single_core! {
+            if cfg!(debug_assertions) {
+                match $crate::current_interrupt() {
+                    Some($interrupt) => {}
+                    _ => panic!(concat!(
+                        "not running in the handler for ",
+                        ::core::stringify!($interrupt)
+                    )),
+                }
+            }
+        }
+    }};
+}
+
+/// Defines a [`StaticHandler`] in a `static` item.
+///
+/// The handler can be given as the path of a function, or as a closure that does not capture
+/// anything. The resulting `static` can be registered using [`Scope::register_static`].
+///
+/// # Examples
+///
+/// ```
+/// use irq::{scope, scoped_interrupts, static_handler};
+/// use mock_pac::interrupt;
+/// use core::sync::atomic::{AtomicU32, Ordering};
+///
+/// scoped_interrupts! {
+///     enum Interrupt {
+///         INT0,
+///         INT1,
+///     }
+///
+///     use #[interrupt];
+/// }
+///
+/// static EVENTS: AtomicU32 = AtomicU32::new(0);
+///
+/// static_handler!(COUNT_EVENT = || {
+///     EVENTS.fetch_add(1, Ordering::Relaxed);
+/// });
+///
+/// fn main() {
+///     scope(|scope| {
+///         scope.register_static(Interrupt::INT0, &COUNT_EVENT);
+///         scope.register_static(Interrupt::INT1, &COUNT_EVENT);
+///     });
+/// }
+/// ```
+///
+/// [`StaticHandler`]: struct.StaticHandler.html
+/// [`Scope::register_static`]: struct.Scope.html#method.register_static
+#[macro_export]
+macro_rules! static_handler {
+    ($( #[$attr:meta] )* $v:vis $name:ident = $f:expr) => {
+        $( #[$attr] )*
+        $v static $name: $crate::StaticHandler = $crate::StaticHandler::new($f);
+    };
+}
+
+/// Creates a scope in which interrupt handlers using stack-local data can be registered.
+///
+/// When this function returns, all handlers will be deregistered again. Note that `scope` calls
+/// using the same interrupt type `I` should not be nested, or all of its interrupt handlers will be
+/// deregistered as soon as the *inner* call returns. Use [subsets] to nest scopes, or to run
+/// independent scopes concurrently.
+///
+/// Handlers are also deregistered when `f` panics and the panic unwinds out of `scope`, before
+/// any of the data borrowed by the handlers is freed.
+///
+/// On multi-core systems, a handler might still be running on another core while the scope is
+/// exited. Enable the `multi-core` Cargo feature to make `scope` wait until all such handlers have
+/// returned before it does. The wait cannot distinguish a handler running on another core from one
+/// that was preempted on the current core, so with that feature, a scope must not be exited from
+/// code that can preempt one of its handlers (such as a higher-priority interrupt handler), or it
+/// will deadlock.
+///
+/// # Collecting results from handlers
+///
+/// Since all handlers are deregistered when `scope` returns, any data they borrowed mutably is
+/// accessible again afterwards. This means that handlers can pass results back to the caller by
+/// simply writing to local variables, without needing any extra shared state:
+///
+/// ```
+/// use irq::{handler, scope, scoped_interrupts};
+/// use mock_pac::interrupt;
+///
+/// scoped_interrupts! {
+///     enum Interrupt {
+///         INT0,
+///     }
+///
+///     use #[interrupt];
+/// }
+///
+/// # fn read_sample() -> u32 { 0 }
+/// fn main() {
+///     let mut samples = [0; 16];
+///     let mut count = 0;
+///
+///     handler!(sampler = || {
+///         if count < samples.len() {
+///             samples[count] = read_sample();
+///             count += 1;
+///         }
+///     });
+///
+///     scope(|scope| {
+///         scope.register(Interrupt::INT0, sampler);
+///
+///         // Start the measurement and wait for it to complete...
+///     });
+///
+///     // The handler is gone, so the results can be used directly.
+///     let collected = &samples[..count];
+/// # assert!(collected.is_empty());
+/// }
+/// ```
+///
+/// # Handing peripherals to handlers
+///
+/// The same works for peripherals: instead of moving them into a `static Mutex<RefCell<Option<_>>>`
+/// so that an interrupt handler can reach them, keep them in local variables and let every handler
+/// borrow the peripherals it needs. The borrow checker makes sure that each peripheral is only
+/// used by one handler at a time, and all of them can be used by the caller again once the scope
+/// has ended:
+///
+/// ```
+/// use irq::{handler, scope, scoped_interrupts};
+/// use mock_pac::interrupt;
+///
+/// scoped_interrupts! {
+///     enum Interrupt {
+///         INT0,
+///         INT1,
+///     }
+///
+///     use #[interrupt];
+/// }
+///
+/// # struct Timer; impl Timer { fn clear_flag(&mut self) {} fn stop(&mut self) {} }
+/// # struct Uart; impl Uart { fn read(&mut self) -> u8 { 0 } }
+/// # struct Peripherals { TIMER0: Timer, UART0: Uart, GPIO: () }
+/// # fn take_peripherals() -> Peripherals { Peripherals { TIMER0: Timer, UART0: Uart, GPIO: () } }
+/// fn main() {
+///     let p = take_peripherals();
+///     let mut timer = p.TIMER0;
+///     let mut uart = p.UART0;
+///     let gpio = p.GPIO;
+///
+///     // Only this handler uses the timer.
+///     handler!(tick = || timer.clear_flag());
+///     // ...and only this one uses the UART.
+///     handler!(rx = || {
+///         let _byte = uart.read();
+///     });
+///
+///     scope(|scope| {
+///         scope.register(Interrupt::INT0, tick);
+///         scope.register(Interrupt::INT1, rx);
+///
+///         // Peripherals that were not handed to a handler can be used here.
+///         let _gpio = &gpio;
+///     });
+///
+///     // The handlers are gone, so their peripherals are available again.
+///     timer.stop();
+/// }
+/// ```
+///
+/// # Sharing state between handlers
+///
+/// State that is needed by many handlers (eg. common board state) can be collected in a context
+/// struct that all of them borrow. Since handlers may preempt each other and the code in the
+/// scope, the context is shared immutably, and must use atomics (or other `Sync` types with
+/// interior mutability) for any state that changes:
+///
+/// ```
+/// use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+/// use irq::{handler, scope, scoped_interrupts};
+/// use mock_pac::interrupt;
+///
+/// scoped_interrupts! {
+///     enum Interrupt {
+///         INT0,
+///         INT1,
+///     }
+///
+///     use #[interrupt];
+/// }
+///
+/// struct Board {
+///     errors: AtomicU32,
+///     low_power: AtomicBool,
+/// }
+///
+/// fn main() {
+///     let board = Board {
+///         errors: AtomicU32::new(0),
+///         low_power: AtomicBool::new(false),
+///     };
+///     let board = &board;
+///
+///     handler!(uart = move || {
+///         if !board.low_power.load(Ordering::Relaxed) {
+///             // This handler is the only one writing `errors`, so no atomic RMW is needed.
+///             let errors = board.errors.load(Ordering::Relaxed);
+///             board.errors.store(errors + 1, Ordering::Relaxed);
+///         }
+///     });
+///     handler!(power = move || board.low_power.store(true, Ordering::Relaxed));
+///
+///     scope(|scope| {
+///         scope.register(Interrupt::INT0, uart);
+///         scope.register(Interrupt::INT1, power);
+///     });
+/// }
+/// ```
+///
+/// # Arming handlers from other handlers
+///
+/// Handlers cannot register other handlers, since the [`Scope`] is not available to them, and
+/// since a handler registered from within an interrupt would have to outlive the scope anyway.
+/// Instead, register all handlers up front and [pause] the ones that should not run yet. Any
+/// handler can then arm (or disarm) another one using [`Interrupt::set_paused`]:
+///
+/// ```
+/// use irq::{handler, scope, scoped_interrupts, Interrupt as _};
+/// use mock_pac::interrupt;
+///
+/// scoped_interrupts! {
+///     enum Interrupt {
+///         INT0,
+///         INT1,
+///     }
+///
+///     use #[interrupt];
+/// }
+///
+/// fn main() {
+///     // A start-of-frame interrupt arms the data interrupt.
+///     handler!(start_of_frame = || Interrupt::INT1.set_paused(false));
+///     handler!(data = || {
+///         // Read the data...
+///     });
+///
+///     scope(|scope| {
+///         scope.register(Interrupt::INT0, start_of_frame);
+///         scope.register(Interrupt::INT1, data);
+///         scope.pause(Interrupt::INT1);
+///
+///         // ...
+///     });
+/// }
+/// ```
+///
+/// When the `multi-core` feature is enabled, pausing an interrupt waits for its handler to return.
+/// A handler must then not pause its own interrupt, or any interrupt whose handler it can
+/// preempt on the same core, since the paused handler can't return until it does.
+///
+/// [subsets]: macro.scoped_interrupts.html#subsets
+/// [`Scope`]: struct.Scope.html
+/// [pause]: struct.Scope.html#method.pause
+/// [`Interrupt::set_paused`]: trait.Interrupt.html#tymethod.set_paused
+pub fn scope<'env, I, F, R>(f: F) -> R
+where
+    I: Interrupt,
+    F: FnOnce(&Scope<'env, I>) -> R,
+{
+    let scope = Scope { _p: PhantomData };
+
+    // If `f` panics, the scope is dropped during unwinding, which deregisters all handlers just like
+    // the explicit `drop` below.
+    let result = f(&scope);
+
+    // Drop the scope, deregistering all interrupt handlers. This is required for soundness: Any
+    // handler passed to `scope` only lives as long as `'env`, which may end right after this
+    // function returns.
+    drop(scope);
+
+    result
+}
+
+/// An interrupt scope created by the [`scope`] function.
+///
+/// [`scope`]: fn.scope.html
+#[allow(missing_debug_implementations)]
+pub struct Scope<'env, I: Interrupt> {
+    // Make `'env` invariant
+    _p: PhantomData<(I, &'env mut &'env ())>,
+}
+
+impl<'env, I: Interrupt> Scope<'env, I> {
+    /// Registers an interrupt handler for the duration of this scope.
+    ///
+    /// Once the enclosing [`scope`] call returns, all interrupts that were registered using this
+    /// method will be deregistered again.
+    ///
+    /// # Parameters
+    ///
+    /// * `interrupt`: The interrupt to handle. This must be a variant of an enum generated by the
+    ///   [`scoped_interrupts!`] macro.
+    /// * `handler`: The handler closure to hook up to the interrupt. For convenience, this can be
+    ///   created using the [`handler!`] macro.
+    ///
+    /// [`scope`]: fn.scope.html
+    /// [`scoped_interrupts!`]: macro.scoped_interrupts.html
+    /// [`handler!`]: macro.handler.html
+    #[inline]
+    pub fn register(&self, interrupt: I, handler: &'env mut Handler<'env>) {
+        // `handler` must be taken by mutable reference here, or the same handler could be
+        // registered for 2 different interrupts.
+        // Its lifetime must also be forced to `'env` to indicate that it may flow into `self`.
+        unsafe {
+            interrupt.register(handler);
+        }
+    }
+
+    /// Registers a [`StaticHandler`] for the duration of this scope.
+    ///
+    /// Unlike closure-based handlers, a `StaticHandler` does not have any mutable state, so it may
+    /// be registered for any number of interrupts at once.
+    ///
+    /// [`StaticHandler`]: struct.StaticHandler.html
+    #[inline]
+    pub fn register_static(&self, interrupt: I, handler: &'static StaticHandler) {
+        unsafe {
+            interrupt.register(&handler.0);
+        }
+    }
+
+    /// Temporarily stops invoking the handler registered for `interrupt`.
+    ///
+    /// The registration itself is kept, and delivery can be resumed with [`resume`]. This allows
+    /// quiescing a handler, for example while the peripheral triggering it is reconfigured.
+    ///
+    /// Note that this does not mask the interrupt in the interrupt controller. While paused, the
+    /// interrupt still fires but returns immediately without clearing its source. Level-triggered
+    /// interrupts should therefore also be disabled at the peripheral to avoid being re-entered
+    /// continuously.
+    ///
+    /// When the scope ends, all interrupts are resumed again.
+    ///
+    /// With the `multi-core` feature, this waits for running invocations of the handler to return.
+    /// Calling it while the handler of `interrupt` is running on the same core (eg. from a
+    /// higher-priority interrupt that preempted it) therefore deadlocks.
+    ///
+    /// [`resume`]: #method.resume
+    #[inline]
+    pub fn pause(&self, interrupt: I) {
+        interrupt.set_paused(true);
+    }
+
+    /// Resumes invoking the handler registered for `interrupt` after a call to [`pause`].
+    ///
+    /// [`pause`]: #method.pause
+    #[inline]
+    pub fn resume(&self, interrupt: I) {
+        interrupt.set_paused(false);
+    }
+
+    /// Returns whether delivery of `interrupt` is currently paused.
+    #[inline]
+    pub fn is_paused(&self, interrupt: I) -> bool {
+        interrupt.is_paused()
+    }
+
+    /// Pauses all interrupts in `interrupts`, as if by calling [`pause`] for each of them.
+    ///
+    /// [`pause`]: #method.pause
+    pub fn pause_set(&self, interrupts: InterruptSet<I>) {
+        for interrupt in interrupts {
+            interrupt.set_paused(true);
+        }
+    }
+
+    /// Resumes all interrupts in `interrupts`, as if by calling [`resume`] for each of them.
+    ///
+    /// [`resume`]: #method.resume
+    pub fn resume_set(&self, interrupts: InterruptSet<I>) {
+        for interrupt in interrupts {
+            interrupt.set_paused(false);
+        }
+    }
+
+    /// Runs `f` with delivery of all interrupts of this scope paused.
+    ///
+    /// This allows performing several registrations as one batch: No handler registered by `f`
+    /// runs before all of them are registered. When `f` returns, every interrupt is resumed,
+    /// unless it was already paused before calling `batch`.
+    ///
+    /// Interrupts are paused as if by [`pause`], not masked in the interrupt controller. An
+    /// interrupt that fires while `f` is running is therefore **not** deferred until the batch
+    /// ends: its handler is not invoked for that event, and the event is lost. Edge-triggered
+    /// sources that may fire during the batch have to be polled or re-raised afterwards, and
+    /// level-triggered sources keep re-entering the veneer until `f` returns.
+    ///
+    /// When `f` returns, the paused state from before the call is restored for all interrupts.
+    /// Calls to [`pause`], [`resume`], [`pause_set`] or [`resume_set`] made by `f` are therefore
+    /// undone: An interrupt paused by `f` is resumed again, unless it was paused before. Such calls
+    /// should be made after `batch` returns instead.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use irq::{handler, scope, scoped_interrupts};
+    /// use mock_pac::interrupt;
+    ///
+    /// scoped_interrupts! {
+    ///     enum Interrupt {
+    ///         INT0,
+    ///         INT1,
+    ///     }
+    ///
+    ///     use #[interrupt];
+    /// }
+    ///
+    /// fn main() {
+    ///     handler!(rx = || {});
+    ///     handler!(tx = || {});
+    ///
+    ///     scope(|scope| {
+    ///         scope.batch(|scope| {
+    ///             // Neither handler runs before both are registered.
+    ///             scope.register(Interrupt::INT0, rx);
+    ///             scope.register(Interrupt::INT1, tx);
+    ///         });
+    ///     });
+    /// }
+    /// ```
+    ///
+    /// [`pause`]: #method.pause
+    /// [`resume`]: #method.resume
+    /// [`pause_set`]: #method.pause_set
+    /// [`resume_set`]: #method.resume_set
+    pub fn batch<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&Self) -> R,
+    {
+        let unpaused = I::pause_all();
+        let result = f(self);
+        I::restore_paused(unpaused);
+        result
+    }
+}
+
+impl<'env, I: Interrupt> Drop for Scope<'env, I> {
+    fn drop(&mut self) {
+        I::deregister_all();
+    }
+}
+
+/// Wraps a closure used as an interrupt handler.
+///
+/// A `Handler` needs to be passed to [`Scope::register`] to do anything.
+///
+/// A `Handler` consists of two words: a pointer to the function that calls the wrapped closure,
+/// and a pointer to the closure's state. Unlike a `dyn FnMut` pointer, this layout is the same on
+/// every target and easy to inspect in a debugger.
+///
+/// [`Scope::register`]: struct.Scope.html#method.register
+pub struct Handler<'a> {
+    /// Invokes the handler, given `data`.
+    call: unsafe fn(*mut ()),
+    /// The closure borrowed mutably for `'a`, or the `fn()` passed to `from_fn`.
+    data: *mut (),
+    /// Makes `'a` invariant, like `&'a mut &'a ()` would, but can be named in a `const fn` on
+    /// Rust 1.61.
+    _p: PhantomData<fn(&'a ()) -> &'a ()>,
+}
+
+/// Calls the handler of type `F` that `data` points to.
+unsafe fn call_closure<F: IrqHandler>(data: *mut ()) {
+    (*(data as *mut F)).on_interrupt()
+}
+
+/// Calls the pinned handler of type `F` that `data` points to.
+unsafe fn call_pinned<F: PinnedIrqHandler>(data: *mut ()) {
+    Pin::new_unchecked(&mut *(data as *mut F)).on_interrupt()
+}
+
+/// Calls the `fn()` stored in `data`.
+unsafe fn call_fn(data: *mut ()) {
+    let f: fn() = mem::transmute(data);
+    f()
+}
+
+impl<'a> Handler<'a> {
+    /// Creates a new interrupt handler wrapper given a closure or any other [`IrqHandler`].
+    ///
+    /// The handler must be `Send`, since it is invoked in interrupt context, which might be on a
+    /// different core than the one registering it.
+    ///
+    /// [`IrqHandler`]: trait.IrqHandler.html
+    #[inline(always)]
+    pub fn new<F>(f: &'a mut F) -> Self
+    where
+        F: IrqHandler + Send + 'a,
+    {
+        Self {
+            call: call_closure::<F>,
+            data: f as *mut F as *mut (),
+            _p: PhantomData,
+        }
+    }
+
+    /// Creates an interrupt handler wrapper given a pinned [`PinnedIrqHandler`].
+    ///
+    /// This allows using handlers whose state must not be moved, such as ones containing intrusive
+    /// list nodes. The handler is only ever accessed through a `Pin`, so its state stays in place
+    /// for as long as it is registered.
+    ///
+    /// [`PinnedIrqHandler`]: trait.PinnedIrqHandler.html
+    #[inline(always)]
+    pub fn new_pinned<F>(f: Pin<&'a mut F>) -> Self
+    where
+        F: PinnedIrqHandler + Send + 'a,
+    {
+        Self {
+            call: call_pinned::<F>,
+            // Safety: The data is only ever accessed as `Pin<&mut F>` by `call_pinned`.
+            data: unsafe { f.get_unchecked_mut() } as *mut F as *mut (),
+            _p: PhantomData,
+        }
+    }
+
+    /// Creates an interrupt handler that invokes a plain function.
+    ///
+    /// Unlike [`Handler::new`], this does not need a closure stored elsewhere, and can be used in
+    /// const context.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use irq::{scope, scoped_interrupts, Handler};
+    /// use mock_pac::interrupt;
+    ///
+    /// scoped_interrupts! {
+    ///     enum Interrupt {
+    ///         INT0,
+    ///     }
+    ///
+    ///     use #[interrupt];
+    /// }
+    ///
+    /// fn on_int0() {
+    ///     // ...
+    /// }
+    ///
+    /// fn main() {
+    ///     let mut int0 = Handler::from_fn(on_int0);
+    ///
+    ///     scope(|scope| {
+    ///         scope.register(Interrupt::INT0, &mut int0);
+    ///     });
+    /// }
+    /// ```
+    ///
+    /// [`Handler::new`]: #method.new
+    #[inline(always)]
+    pub const fn from_fn(f: fn()) -> Self {
+        Self {
+            call: call_fn,
+            data: f as *mut (),
+            _p: PhantomData,
+        }
+    }
+
+    /// Invokes the interrupt handler closure.
+    #[inline(always)]
+    pub fn invoke(&mut self) {
+        unsafe { Self::invoke_raw(self) }
+    }
+
+    /// Private API for use by the `scoped_interrupts!` macro. Do not use.
+    ///
+    /// # Safety
+    ///
+    /// `this` must point to a live `Handler`. If it wraps a closure, the caller must have unique
+    /// access to that closure for the duration of the call.
+    #[doc(hidden)]
+    #[inline(always)]
+    pub unsafe fn invoke_raw(this: *const Self) {
+        // Only shared access to the `Handler` itself is needed, which allows `StaticHandler`s to
+        // live in immutable statics.
+        ((*this).call)((*this).data)
+    }
+}
+
+impl<'a> fmt::Debug for Handler<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "handler@{:p}", self as *const _)
+    }
+}
+
+/// An interrupt handler that can be stored in a `static`.
+///
+/// A `StaticHandler` wraps a plain function (or a closure that captures nothing). It is created in
+/// const context, so it is placed in read-only memory and needs no setup code at runtime. It can be
+/// registered by passing it to [`Scope::register_static`].
+///
+/// Usually, `StaticHandler`s are defined using the [`static_handler!`] macro.
+///
+/// [`Scope::register_static`]: struct.Scope.html#method.register_static
+/// [`static_handler!`]: macro.static_handler.html
+#[derive(Debug)]
+pub struct StaticHandler(Handler<'static>);
+
+// Safety: A `StaticHandler` only ever wraps a `fn()`, which has no state that could be shared.
+unsafe impl Sync for StaticHandler {}
+
+impl StaticHandler {
+    /// Creates a `StaticHandler` that will invoke `f`.
+    #[inline(always)]
+    pub const fn new(f: fn()) -> Self {
+        StaticHandler(Handler::from_fn(f))
+    }
+}
+
+/// Types that can handle interrupts.
+///
+/// This is implemented for all `FnMut()` closures. Implementing it for a driver struct allows
+/// registering the driver itself as a handler, keeping its state in a named type instead of in
+/// variables captured by a closure.
+///
+/// # Examples
+///
+/// ```
+/// use irq::{scope, scoped_interrupts, Handler, IrqHandler};
+/// use mock_pac::interrupt;
+///
+/// scoped_interrupts! {
+///     enum Interrupt {
+///         INT0,
+///     }
+///
+///     use #[interrupt];
+/// }
+///
+/// struct Encoder {
+///     position: i32,
+/// }
+///
+/// impl IrqHandler for Encoder {
+///     fn on_interrupt(&mut self) {
+///         self.position += 1;
+///     }
+/// }
+///
+/// fn main() {
+///     let mut encoder = Encoder { position: 0 };
+///     let mut handler = Handler::new(&mut encoder);
+///
+///     scope(|scope| {
+///         scope.register(Interrupt::INT0, &mut handler);
+///     });
+///
+///     println!("position: {}", encoder.position);
+/// }
+/// ```
+pub trait IrqHandler {
+    /// Handles an occurrence of the interrupt this handler is registered for.
+    fn on_interrupt(&mut self);
+}
+
+impl<F: FnMut()> IrqHandler for F {
+    #[inline(always)]
+    fn on_interrupt(&mut self) {
+        self()
+    }
+}
+
+/// Types that can handle interrupts without being moved.
+///
+/// This is like [`IrqHandler`], but the handler is invoked through a `Pin<&mut Self>`, so it can
+/// be used for `!Unpin` state. It is registered using [`Handler::new_pinned`].
+///
+/// # Examples
+///
+/// ```
+/// use core::marker::PhantomPinned;
+/// use core::pin::Pin;
+/// use irq::{scope, scoped_interrupts, Handler, PinnedIrqHandler};
+/// use mock_pac::interrupt;
+///
+/// scoped_interrupts! {
+///     enum Interrupt {
+///         INT0,
+///     }
+///
+///     use #[interrupt];
+/// }
+///
+/// struct Node {
+///     events: u32,
+///     // Other data structures might point to this node.
+///     _pinned: PhantomPinned,
+/// }
+///
+/// impl PinnedIrqHandler for Node {
+///     fn on_interrupt(self: Pin<&mut Self>) {
+///         // Safety: `events` is not structurally pinned.
+///         unsafe { self.get_unchecked_mut().events += 1 };
+///     }
+/// }
+///
+/// fn main() {
+///     let mut node = Node { events: 0, _pinned: PhantomPinned };
+///     // Safety: `node` is shadowed, so it cannot be moved after this.
+///     let mut node = unsafe { Pin::new_unchecked(&mut node) };
+///     let mut handler = Handler::new_pinned(node.as_mut());
+///
+///     scope(|scope| {
+///         scope.register(Interrupt::INT0, &mut handler);
+///     });
+///
+///     println!("events: {}", node.events);
+/// }
+/// ```
+///
+/// [`IrqHandler`]: trait.IrqHandler.html
+/// [`Handler::new_pinned`]: struct.Handler.html#method.new_pinned
+pub trait PinnedIrqHandler {
+    /// Handles an occurrence of the interrupt this handler is registered for.
+    fn on_interrupt(self: Pin<&mut Self>);
+}
+
+/// Private API for use by the `scoped_interrupts!` macro. Do not use.
+#[doc(hidden)]
+pub struct HandlerAddr {
+    addr: AtomicUsize,
+    /// Whether delivery to the handler is paused.
+    paused: AtomicBool,
+    /// Whether the handler is currently being invoked.
+    #[cfg(feature = "multi-core")]
+    running: AtomicBool,
+}
+
+impl HandlerAddr {
+    #[allow(clippy::new_without_default)]
+    #[inline(always)]
+    pub const fn new() -> Self {
+        Self {
+            addr: AtomicUsize::new(0),
+            paused: AtomicBool::new(false),
+            #[cfg(feature = "multi-core")]
+            running: AtomicBool::new(false),
+        }
+    }
+
+    /// Marks the handler as running for as long as the returned guard is alive.
+    #[inline(always)]
+    pub fn enter(&self) -> InFlight<'_> {
+        #[cfg(feature = "multi-core")]
+        {
+            // This forms a Dekker-style handshake with `wait_idle`: Either we observe the cleared
+            // address, or `wait_idle` observes `running` and waits for us.
+            self.running.store(true, Ordering::Relaxed);
+            atomic::fence(Ordering::SeqCst);
+        }
+
+        InFlight { slot: self }
+    }
+
+    /// Waits until no invocation of the previously stored handler is in progress.
+    ///
+    /// With the `multi-core` feature, this spins until the handler returns, even if it was
+    /// preempted by the caller on the same core, in which case it never does.
+    ///
+    /// Without the `multi-core` feature, this does nothing, since a handler that preempted the
+    /// caller must have finished before the caller can run again.
+    #[inline(always)]
+    pub fn wait_idle(&self) {
+        #[cfg(feature = "multi-core")]
+        {
+            atomic::fence(Ordering::SeqCst);
+            while self.running.load(Ordering::Acquire) {
+                hint::spin_loop();
+            }
+        }
+    }
+
+    #[inline(always)]
+    pub fn load(&self) -> usize {
+        self.addr.load(Ordering::Acquire)
+    }
+
+    /// # Safety
+    ///
+    /// `addr` must be 0 or point to a `Handler` that stays valid until the address is replaced.
+    #[inline(always)]
+    pub unsafe fn store(&self, addr: usize) {
+        self.addr.store(addr, Ordering::Release)
+    }
+
+    #[inline(always)]
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+
+    /// Pauses or resumes the handler. When pausing, this waits for any running invocation to
+    /// finish.
+    #[inline(always)]
+    pub fn set_paused(&self, paused: bool) {
+        self.paused.store(paused, Ordering::Relaxed);
+        if paused {
+            self.wait_idle();
+        }
+    }
+
+    /// Invokes the registered handler, unless delivery is paused. The slot is recorded as the
+    /// active one while the handler runs.
+    ///
+    /// Returns `false` if no handler is registered.
+    ///
+    /// # Safety
+    ///
+    /// This must only be called when the interrupt this slot belongs to fires (or is dispatched),
+    /// and not reentrantly.
+    // Inlined into the `__dispatch` function generated once per interrupt enum, which is what the
+    // veneers call. This keeps the veneers small at the cost of a single direct call per interrupt.
+    #[inline(always)]
+    pub unsafe fn dispatch(&self) -> bool {
+        // Mark the handler as in-flight while it runs, so that deregistering it can wait for it to
+        // finish on multi-core systems.
+        let in_flight = self.enter();
+        let handler = in_flight.addr();
+        if handler == 0 {
+            return false;
+        }
+
+        if self.is_paused() {
+            // Delivery was paused using `Scope::pause`, so ignore the interrupt.
+        } else {
+            let handler = handler as *const Handler<'_>;
+
+            // Record the running interrupt for `Interrupt::current()` until the handler returns.
+            #[cfg(not(feature = "multi-core"))]
+            let _active = ACTIVE.enter(self);
+
+            // Soundness:
+            // - Relies on the user-facing API to manage the handler lifetime (which is
+            //   dangling here).
+            // - Relies on interrupts not being reentrant
+            Handler::invoke_raw(handler);
+        }
+
+        true
+    }
+
+    /// Returns whether the handler in this slot is the innermost one currently running.
+    #[cfg(not(feature = "multi-core"))]
+    #[inline(always)]
+    pub fn is_active(&self) -> bool {
+        ACTIVE.slot.load(Ordering::Relaxed) == self as *const Self as usize
+    }
+}
+
+impl fmt::Debug for HandlerAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "active handler@{:p}", self.load() as *const ())
+    }
+}
+
+/// Private API for use by the `scoped_interrupts!` macro. Do not use.
+#[doc(hidden)]
+#[derive(Debug)]
+pub struct InFlight<'a> {
+    slot: &'a HandlerAddr,
+}
+
+impl<'a> InFlight<'a> {
+    #[inline(always)]
+    pub fn addr(&self) -> usize {
+        self.slot.load()
+    }
+}
+
+impl<'a> Drop for InFlight<'a> {
+    #[inline(always)]
+    fn drop(&mut self) {
+        #[cfg(feature = "multi-core")]
+        self.slot.running.store(false, Ordering::Release);
+    }
+}
+
+/// The slot of the innermost handler that is currently running, shared by all interrupt enums.
+///
+/// This state is only ever observed by the core that executes the interrupt handlers, so `Relaxed`
+/// ordering is sufficient. That only holds as long as all handlers run on the same core, which is
+/// why nothing is recorded with the `multi-core` feature, where `current` always returns `None`.
+#[cfg(not(feature = "multi-core"))]
+static ACTIVE: ActiveInterrupt = ActiveInterrupt {
+    slot: AtomicUsize::new(0),
+};
+
+/// Tracks which handler slot is currently executing its handler.
+#[cfg(not(feature = "multi-core"))]
+struct ActiveInterrupt {
+    /// Address of the `HandlerAddr` of the running handler, or 0 when none is running.
+    slot: AtomicUsize,
+}
+
+#[cfg(not(feature = "multi-core"))]
+impl ActiveInterrupt {
+    #[inline(always)]
+    fn enter(&self, slot: &HandlerAddr) -> ActiveGuard<'_> {
+        let prev = self.slot.load(Ordering::Relaxed);
+        self.slot
+            .store(slot as *const HandlerAddr as usize, Ordering::Relaxed);
+        ActiveGuard { active: self, prev }
+    }
+}
+
+/// Restores the previously running handler when dropped, which makes nested interrupts report
+/// the right value.
+#[cfg(not(feature = "multi-core"))]
+struct ActiveGuard<'a> {
+    active: &'a ActiveInterrupt,
+    prev: usize,
+}
+
+#[cfg(not(feature = "multi-core"))]
+impl<'a> Drop for ActiveGuard<'a> {
+    #[inline(always)]
+    fn drop(&mut self) {
+        self.active.slot.store(self.prev, Ordering::Relaxed);
+    }
+}
+
+/// Returns the hooked interrupt whose handler is currently executing.
+///
+/// This returns `None` when called from outside of an interrupt handler, or from an interrupt
+/// that is not hooked by `I`. When interrupts are nested, only the innermost (ie. currently
+/// running) interrupt is considered. This also holds across interrupt enums: While the handler of
+/// an interrupt of another enum preempts a handler of `I`, this returns `None`.
+///
+/// This is tracked by the veneers generated by [`scoped_interrupts!`] and does not require any
+/// platform support. Note that this only covers code executed by a registered handler closure.
+///
+/// The running interrupt is not tracked per core, so when the `multi-core` feature is enabled,
+/// nothing is tracked at all, and this always returns `None`.
+///
+/// # Examples
+///
+/// ```
+/// use irq::{current_interrupt, scoped_interrupts};
+/// use mock_pac::interrupt;
+///
+/// scoped_interrupts! {
+///     enum Interrupt {
+///         INT0,
+///     }
+///
+///     use #[interrupt];
+/// }
+///
+/// fn called_from_anywhere() {
+///     match current_interrupt::<Interrupt>() {
+///         Some(Interrupt::INT0) => { /* running in the `INT0` handler */ }
+///         None => { /* not running in a hooked interrupt */ }
+///     }
+/// }
+/// # fn main() { called_from_anywhere() }
+/// ```
+///
+/// [`scoped_interrupts!`]: macro.scoped_interrupts.html
+#[inline]
+pub fn current_interrupt<I: Interrupt>() -> Option<I> {
+    I::current()
+}
+
+/// Trait for interrupt enums generated by [`scoped_interrupts!`].
+///
+/// # Safety
+///
+/// This trait is unsafe to implement. Safely implementing it requires correctly implementing its
+/// methods. In particular, `deregister_all` must, in fact, deregister all registered handlers.
+///
+/// It is recommended to use [`scoped_interrupts!`] instead of implementing this trait by hand.
+/// Required items may be added to this trait in future releases, which is a breaking change only
+/// for manual implementations.
+///
+/// [`scoped_interrupts!`]: macro.scoped_interrupts.html
+pub unsafe trait Interrupt {
+    /// Registers a `handler` to handle interrupts of type `self`.
+    ///
+    /// # Safety
+    ///
+    /// This is only safe to call if the caller ensures that the handler is not invoked after its
+    /// lifetime expires. If the handler wraps a closure, the caller must also ensure that it is not
+    /// registered for more than one interrupt at a time.
+    unsafe fn register(self, handler: &Handler<'_>);
+
+    /// Deregisters all interrupts that were registered using `register`.
+    ///
+    /// This must reset the global interrupt state to its default/startup/reset values, where no
+    /// interrupt handlers are registered.
+    ///
+    /// When the `multi-core` feature is enabled, this must also wait for any handlers that are
+    /// still executing on other cores to return. Since that includes handlers preempted on the
+    /// current core, this deadlocks when called from code that preempted a registered handler.
+    fn deregister_all();
+
+    /// Returns the interrupt whose handler is currently executing, if any.
+    ///
+    /// Also see [`current_interrupt`]. The default implementation always returns `None`, which is
+    /// also what enums generated by [`scoped_interrupts!`] do with the `multi-core` feature.
+    ///
+    /// [`current_interrupt`]: fn.current_interrupt.html
+    /// [`scoped_interrupts!`]: macro.scoped_interrupts.html
+    fn current() -> Option<Self>
+    where
+        Self: Sized,
+    {
+        None
+    }
+
+    /// Pauses or resumes delivery of `self` to its registered handler.
+    ///
+    /// While paused, the hooked interrupt returns immediately without invoking the handler. When
+    /// pausing on a system with the `multi-core` feature enabled, this must wait for running
+    /// invocations of the handler to finish. Pausing from code that preempted the handler on the
+    /// same core then deadlocks.
+    fn set_paused(&self, paused: bool);
+
+    /// Returns whether delivery of `self` is paused.
+    fn is_paused(&self) -> bool;
+
+    /// The number of interrupts represented by this type.
+    const COUNT: usize;
+
+    /// Returns the position of `self` among all interrupts of this type.
+    ///
+    /// This must be less than `COUNT`, and must be different for every interrupt.
+    fn index(&self) -> usize;
+
+    /// Returns the interrupt at position `index`, or `None` if `index` is out of range.
+    ///
+    /// This is the inverse of `index`.
+    fn from_index(index: usize) -> Option<Self>
+    where
+        Self: Sized;
+
+    /// Invokes the handler registered for `self`, as if the interrupt had fired.
+    ///
+    /// This is what the veneer of a hooked interrupt does. It can be used to dispatch virtual
+    /// interrupts, or to simulate interrupts in tests.
+    ///
+    /// # Safety
+    ///
+    /// Interrupt handlers are not reentrant. This must not be called while the handler of `self`
+    /// is already running, for example from within that handler or concurrently on another core.
+    unsafe fn dispatch(&self);
+
+    /// Pauses delivery of all interrupts of this type.
+    ///
+    /// This is useful around phases where no handler may run, such as flash programming or clock
+    /// reconfiguration. Returns the set of interrupts that were not paused before, which can be
+    /// passed to [`restore_paused`] to return to the previous state afterwards.
+    ///
+    /// Like [`set_paused`], this does not mask the interrupts in the interrupt controller. They
+    /// still fire, but return immediately without invoking their handler.
+    ///
+    /// [`restore_paused`]: #method.restore_paused
+    /// [`set_paused`]: #tymethod.set_paused
+    fn pause_all() -> InterruptSet<Self>
+    where
+        Self: Sized,
+    {
+        let unpaused = Self::unpaused();
+        for interrupt in InterruptSet::<Self>::all().iter() {
+            interrupt.set_paused(true);
+        }
+        unpaused
+    }
+
+    /// Resumes delivery of all interrupts of this type.
+    ///
+    /// Returns the set of interrupts that were not paused before.
+    fn resume_all() -> InterruptSet<Self>
+    where
+        Self: Sized,
+    {
+        Self::restore_paused(InterruptSet::all())
+    }
+
+    /// Resumes delivery of the interrupts in `unpaused`, and pauses all others.
+    ///
+    /// Returns the set of interrupts that were not paused before.
+    fn restore_paused(unpaused: InterruptSet<Self>) -> InterruptSet<Self>
+    where
+        Self: Sized,
+    {
+        let previous = Self::unpaused();
+        for interrupt in InterruptSet::<Self>::all().iter() {
+            let paused = !unpaused.contains(&interrupt);
+            interrupt.set_paused(paused);
+        }
+        previous
+    }
+
+    /// Returns the set of interrupts of this type whose delivery is not paused.
+    fn unpaused() -> InterruptSet<Self>
+    where
+        Self: Sized,
+    {
+        (0..Self::COUNT)
+            .filter_map(Self::from_index)
+            .filter(|interrupt| !interrupt.is_paused())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Interrupt as _;
+    use super::*;
+    use std::panic::catch_unwind;
+
+    scoped_interrupts! {
+        enum Interrupt {
+            Int0,
+            Int1 use #[export_name = "Int1"],
+        }
+
+        use #[no_mangle];
+
+        subset First {
+            Int0,
+        }
+
+        subset Second {
+            Int1,
+        }
+
+        group BOTH {
+            Int0,
+            Int1,
+        }
+    }
+
+    struct Test {}
+
+    #[derive(Debug)]
+    struct Panicked {}
+
+    impl Test {
+        fn raise_interrupt(&mut self, int: Interrupt) -> Result<(), Panicked> {
+            catch_unwind(|| match int {
+                Interrupt::Int0 => unsafe { Int0() },
+                Interrupt::Int1 => unsafe { Int1() },
+            })
+            .map_err(|_| Panicked {})
+        }
+    }
+
+    impl Drop for Test {
+        fn drop(&mut self) {
+            // Reset
+            Interrupt::deregister_all();
+        }
+    }
+
+    fn test(f: impl FnOnce(&mut Test)) {
+        // Lock a mutex for each test, ensuring that they run sequentially. This is required since
+        // they mutate shared state.
+        // Miri detects this as a leak (which I guess is kinda true), so don't do this on Miri. It
+        // doesn't support threads anyways.
+        #[cfg(not(miri))]
+        let _guard = {
+            use once_cell::sync::OnceCell;
+            use std::sync::Mutex;
+
+            static MUTEX: OnceCell<Mutex<()>> = OnceCell::new();
+            MUTEX
+                .get_or_init(|| Mutex::new(()))
+                .lock()
+                .unwrap_or_else(|e| e.into_inner()) // drink the poison
+        };
+
+        let mut test = Test {};
+
+        f(&mut test);
+    }
+
+    #[test]
+    #[cfg(not(feature = "panic-never"))]
+    fn not_registered() {
+        test(|test| {
+            test.raise_interrupt(Interrupt::Int0).unwrap_err();
+            test.raise_interrupt(Interrupt::Int1).unwrap_err();
+            test.raise_interrupt(Interrupt::Int0).unwrap_err();
+            test.raise_interrupt(Interrupt::Int1).unwrap_err();
+        });
+    }
+
+    #[test]
+    #[cfg(not(feature = "panic-never"))]
+    fn not_registered_message() {
+        test(|_| {
+            let payload = catch_unwind(|| unsafe { Int1() }).unwrap_err();
+            assert_eq!(
+                payload.downcast_ref::<String>().map(|s| &**s),
+                Some("no handler registered for Int1")
+            );
+        });
+    }
+
+    #[test]
+    #[cfg(feature = "panic-never")]
+    fn not_registered_spins() {
+        use std::thread;
+        use std::time::Duration;
+
+        test(|_| {
+            let mut i = 0;
+            handler!(handler = || i += 1);
+
+            // The veneer spins until a handler is registered, and then returns without invoking
+            // it.
+            let thread = thread::spawn(|| unsafe { Int0() });
+            thread::sleep(Duration::from_millis(50));
+            assert!(!thread.is_finished());
+
+            scope(|scope| {
+                scope.register(Interrupt::Int0, handler);
+                thread.join().unwrap();
+            });
+
+            assert_eq!(i, 0);
+        })
+    }
+
+    #[test]
+    #[cfg(not(feature = "panic-never"))]
+    fn deregistered_on_panic() {
+        use std::panic::AssertUnwindSafe;
+
+        test(|test| {
+            let mut i = 0;
+
+            let result = catch_unwind(AssertUnwindSafe(|| {
+                handler!(handler = || i += 1);
+
+                scope(|scope| {
+                    scope.register(Interrupt::Int0, handler);
+                    panic!("scope body panicked");
+                })
+            }));
+            assert!(result.is_err());
+
+            test.raise_interrupt(Interrupt::Int0).unwrap_err();
+            assert_eq!(i, 0);
+        });
+    }
+
+    #[test]
+    #[cfg(not(feature = "panic-never"))]
+    fn simple() {
+        test(|test| {
+            let mut i = 0;
+
+            // Having to declare (store) the handler *outside* the call to `scope` is a natural
+            // property of this approach, since it might be called anytime until `scope` returns.
+            let mut closure = || {
+                i += 1;
+            };
+            let mut handler = Handler::new(&mut closure);
+
+            // (this is verbose, normally one would use `handler!`)
+
+            scope(|scope| {
+                // Non-'env handler would be unsound.
+                scope.register(Interrupt::Int0, &mut handler);
+
+                // Test that the handler is called when the interrupt is raised.
+                test.raise_interrupt(Interrupt::Int0).unwrap();
+            });
+
+            assert_eq!(i, 1);
+
+            // Test that the end of the scope deregisters the interrupt.
+            test.raise_interrupt(Interrupt::Int0).unwrap_err();
+            assert_eq!(i, 1);
+        });
+    }
+
+    #[test]
+    fn handler_sharing_data() {
+        test(|test| {
+            let shared = vec![0, 1, 2];
+
+            handler!(handler0 = || println!("{:?}", shared));
+            handler!(handler1 = || println!("{:?}", shared));
+
+            scope(|scope| {
+                scope.register(Interrupt::Int0, handler0);
+                scope.register(Interrupt::Int1, handler1);
+
+                test.raise_interrupt(Interrupt::Int0).unwrap();
+                test.raise_interrupt(Interrupt::Int1).unwrap();
+            });
+        })
+    }
+
+    #[test]
+    #[cfg(feature = "multi-core")]
+    fn deregistration_waits_for_running_handler() {
+        use std::sync::atomic::AtomicBool;
+        use std::sync::mpsc;
+        use std::thread;
+        use std::time::Duration;
+
+        test(|_| {
+            let finished = &AtomicBool::new(false);
+            let (entered_tx, entered_rx) = mpsc::channel();
+
+            handler!(
+                handler0 = move || {
+                    entered_tx.send(()).unwrap();
+                    thread::sleep(Duration::from_millis(50));
+                    finished.store(true, Ordering::SeqCst);
+                }
+            );
+
+            let thread = scope(|scope| {
+                scope.register(Interrupt::Int0, handler0);
+
+                // Run the handler on another "core" and exit the scope while it's running.
+                let thread = thread::spawn(|| unsafe { Int0() });
+                entered_rx.recv().unwrap();
+                thread
+            });
+
+            assert!(finished.load(Ordering::SeqCst));
+            thread.join().unwrap();
+        })
+    }
+
+    #[test]
+    fn handler_from_fn() {
+        use std::sync::atomic::AtomicUsize;
+
+        static CALLS: AtomicUsize = AtomicUsize::new(0);
+
+        fn count() {
+            CALLS.fetch_add(1, Ordering::Relaxed);
+        }
+
+        test(|test| {
+            let mut i = 0;
+            handler!(closure = || i += 1);
+            let mut function = Handler::from_fn(count);
+
+            scope(|scope| {
+                scope.register(Interrupt::Int0, closure);
+                scope.register(Interrupt::Int1, &mut function);
+
+                test.raise_interrupt(Interrupt::Int0).unwrap();
+                test.raise_interrupt(Interrupt::Int1).unwrap();
+            });
+
+            assert_eq!(i, 1);
+            assert_eq!(CALLS.load(Ordering::Relaxed), 1);
+        })
+    }
+
+    #[test]
+    fn irq_handler_impl() {
+        struct Counter(u32);
+
+        impl IrqHandler for Counter {
+            fn on_interrupt(&mut self) {
+                self.0 += 1;
+            }
+        }
+
+        test(|test| {
+            let mut counter = Counter(0);
+            let mut handler = Handler::new(&mut counter);
+
+            scope(|scope| {
+                scope.register(Interrupt::Int1, &mut handler);
+                test.raise_interrupt(Interrupt::Int1).unwrap();
+                test.raise_interrupt(Interrupt::Int1).unwrap();
+            });
+
+            assert_eq!(counter.0, 2);
+        })
+    }
+
+    #[test]
+    fn pinned_handler() {
+        use core::marker::PhantomPinned;
+
+        struct Pinned {
+            calls: u32,
+            _pinned: PhantomPinned,
+        }
+
+        impl PinnedIrqHandler for Pinned {
+            fn on_interrupt(self: Pin<&mut Self>) {
+                unsafe { self.get_unchecked_mut().calls += 1 };
+            }
+        }
+
+        test(|test| {
+            let mut pinned = Box::pin(Pinned {
+                calls: 0,
+                _pinned: PhantomPinned,
+            });
+            let mut handler = Handler::new_pinned(pinned.as_mut());
+
+            scope(|scope| {
+                scope.register(Interrupt::Int0, &mut handler);
+                test.raise_interrupt(Interrupt::Int0).unwrap();
+            });
+
+            assert_eq!(pinned.calls, 1);
+        })
+    }
+
+    #[test]
+    fn handler_layout() {
+        assert_eq!(mem::size_of::<Handler<'_>>(), 2 * mem::size_of::<usize>());
+    }
+
+    #[test]
+    #[cfg(not(feature = "panic-never"))]
+    fn static_handler() {
+        use std::sync::atomic::AtomicUsize;
+
+        static CALLS: AtomicUsize = AtomicUsize::new(0);
+
+        static_handler!(
+            COUNT = || {
+                CALLS.fetch_add(1, Ordering::Relaxed);
+            }
+        );
+
+        test(|test| {
+            scope(|scope| {
+                scope.register_static(Interrupt::Int0, &COUNT);
+                scope.register_static(Interrupt::Int1, &COUNT);
+
+                test.raise_interrupt(Interrupt::Int0).unwrap();
+                test.raise_interrupt(Interrupt::Int1).unwrap();
+            });
+
+            assert_eq!(CALLS.load(Ordering::Relaxed), 2);
+            test.raise_interrupt(Interrupt::Int0).unwrap_err();
+        })
+    }
+
+    #[test]
+    fn pause_resume() {
+        test(|test| {
+            let mut i = 0;
+            handler!(handler0 = || i += 1);
+
+            scope(|scope| {
+                scope.register(Interrupt::Int0, handler0);
+
+                scope.pause(Interrupt::Int0);
+                assert!(scope.is_paused(Interrupt::Int0));
+                assert!(!scope.is_paused(Interrupt::Int1));
+                test.raise_interrupt(Interrupt::Int0).unwrap();
+
+                scope.resume(Interrupt::Int0);
+                test.raise_interrupt(Interrupt::Int0).unwrap();
+
+                // Leave it paused, ending the scope must resume it.
+                scope.pause(Interrupt::Int0);
+            });
+
+            assert_eq!(i, 1);
+            assert!(!Interrupt::Int0.is_paused());
+        })
+    }
+
+    #[test]
+    #[cfg(feature = "names")]
+    fn names() {
+        assert_eq!(Interrupt::Int0.name(), "Int0");
+        assert_eq!(Interrupt::Int1.to_string(), "Int1");
+    }
+
+    #[test]
+    fn pause_all() {
+        test(|test| {
+            let mut i = 0;
+            let mut j = 0;
+            handler!(handler0 = || i += 1);
+            handler!(handler1 = || j += 1);
+
+            scope(|scope| {
+                scope.register(Interrupt::Int0, handler0);
+                scope.register(Interrupt::Int1, handler1);
+                scope.pause(Interrupt::Int1);
+
+                let unpaused = Interrupt::pause_all();
+                assert_eq!(unpaused, InterruptSet::from(Interrupt::Int0));
+                assert!(Interrupt::unpaused().is_empty());
+                test.raise_interrupt(Interrupt::Int0).unwrap();
+                test.raise_interrupt(Interrupt::Int1).unwrap();
+
+                assert!(Interrupt::restore_paused(unpaused).is_empty());
+                test.raise_interrupt(Interrupt::Int0).unwrap();
+                test.raise_interrupt(Interrupt::Int1).unwrap();
+
+                assert_eq!(Interrupt::resume_all(), unpaused);
+                test.raise_interrupt(Interrupt::Int1).unwrap();
+            });
+
+            assert_eq!(i, 1);
+            assert_eq!(j, 1);
+        })
+    }
+
+    #[test]
+    fn batch() {
+        test(|test| {
+            let mut i = 0;
+            handler!(handler = || i += 1);
+
+            scope(|scope| {
+                scope.pause(Interrupt::Int1);
+                scope.batch(|scope| {
+                    scope.register(Interrupt::Int0, handler);
+                    test.raise_interrupt(Interrupt::Int0).unwrap();
+                });
+
+                test.raise_interrupt(Interrupt::Int0).unwrap();
+                assert!(scope.is_paused(Interrupt::Int1));
+            });
+
+            assert_eq!(i, 1);
+        })
+    }
+
+    #[test]
+    fn batch_undoes_pause() {
+        test(|test| {
+            let mut i = 0;
+            handler!(handler = || i += 1);
+
+            scope(|scope| {
+                scope.register(Interrupt::Int0, handler);
+                scope.pause(Interrupt::Int1);
+                scope.batch(|scope| {
+                    scope.pause(Interrupt::Int0);
+                    scope.resume(Interrupt::Int1);
+                });
+
+                // The state from before the batch is restored.
+                assert!(!scope.is_paused(Interrupt::Int0));
+                assert!(scope.is_paused(Interrupt::Int1));
+                test.raise_interrupt(Interrupt::Int0).unwrap();
+            });
+
+            assert_eq!(i, 1);
+        })
+    }
+
+    #[test]
+    fn batch_drops_interrupts() {
+        test(|test| {
+            let mut i = 0;
+            handler!(handler = || i += 1);
+
+            scope(|scope| {
+                scope.register(Interrupt::Int0, handler);
+                scope.batch(|_| {
+                    test.raise_interrupt(Interrupt::Int0).unwrap();
+                });
+
+                // The interrupt raised during the batch is not replayed when it ends.
+                assert!(!scope.is_paused(Interrupt::Int0));
+            });
+
+            assert_eq!(i, 0);
+        })
+    }
+
+    #[test]
+    fn groups() {
+        test(|test| {
+            let mut i = 0;
+            let mut j = 0;
+            handler!(handler0 = || i += 1);
+            handler!(handler1 = || j += 1);
+
+            assert_eq!(BOTH, InterruptSet::all());
+            assert_eq!(ALL_VIRTUAL, InterruptSet::all());
+
+            scope(|scope| {
+                scope.register(Interrupt::Int0, handler0);
+                scope.register(Interrupt::Int1, handler1);
+
+                scope.pause_set(BOTH);
+                test.raise_interrupt(Interrupt::Int0).unwrap();
+                test.raise_interrupt(Interrupt::Int1).unwrap();
+
+                scope.resume_set(BOTH);
+                test.raise_interrupt(Interrupt::Int0).unwrap();
+                test.raise_interrupt(Interrupt::Int1).unwrap();
+            });
+
+            assert_eq!(i, 1);
+            assert_eq!(j, 1);
+        })
+    }
+
+    #[test]
+    fn arm_from_handler() {
+        test(|test| {
+            let mut data_calls = 0;
+            handler!(start_of_frame = || Interrupt::Int1.set_paused(false));
+            handler!(data = || data_calls += 1);
+
+            scope(|scope| {
+                scope.register(Interrupt::Int0, start_of_frame);
+                scope.register(Interrupt::Int1, data);
+                scope.pause(Interrupt::Int1);
+
+                test.raise_interrupt(Interrupt::Int1).unwrap();
+                test.raise_interrupt(Interrupt::Int0).unwrap();
+                test.raise_interrupt(Interrupt::Int1).unwrap();
+            });
+
+            assert_eq!(data_calls, 1);
+        })
+    }
+
+    #[test]
+    #[cfg(not(feature = "panic-never"))]
+    fn subsets() {
+        test(|test| {
+            let mut i = 0;
+            let mut j = 0;
+            handler!(handler0 = || i += 1);
+            handler!(handler1 = || j += 1);
+
+            scope(|first| {
+                first.register(First::Int0, handler0);
+
+                scope(|second| {
+                    second.register(Second::Int1, handler1);
+
+                    test.raise_interrupt(Interrupt::Int0).unwrap();
+                    test.raise_interrupt(Interrupt::Int1).unwrap();
+                });
+
+                // Only `Second` was deregistered.
+                test.raise_interrupt(Interrupt::Int0).unwrap();
+                test.raise_interrupt(Interrupt::Int1).unwrap_err();
+            });
+
+            test.raise_interrupt(Interrupt::Int0).unwrap_err();
+            assert_eq!(i, 2);
+            assert_eq!(j, 1);
+        })
     }
-}
 
-impl<'env, I: Interrupt> Drop for Scope<'env, I> {
-    fn drop(&mut self) {
-        I::deregister_all();
+    #[test]
+    #[cfg(not(feature = "multi-core"))]
+    fn subset_current() {
+        test(|test| {
+            let mut seen = None;
+            handler!(
+                handler1 =
+                    || seen = Some((First::current().is_some(), Second::current().is_some()))
+            );
+
+            scope(|scope| {
+                scope.register(Second::Int1, handler1);
+                test.raise_interrupt(Interrupt::Int1).unwrap();
+            });
+
+            assert_eq!(seen, Some((false, true)));
+        })
     }
-}
 
-/// Wraps a closure used as an interrupt handler.
-///
-/// A `Handler` needs to be passed to [`Scope::register`] to do anything.
-///
-/// [`Scope::register`]: struct.Scope.html#method.register
-pub struct Handler<'a> {
-    f: &'a mut dyn FnMut(),
-}
+    mod extern_c {
+        scoped_interrupts! {
+            pub(super) enum Interrupt {
+                ExternInt0,
+            }
 
-impl<'a> Handler<'a> {
-    /// Creates a new interrupt handler wrapper given a closure.
-    #[inline(always)]
-    pub fn new<F>(f: &'a mut F) -> Self
-    where
-        F: FnMut() + Send + 'a,
-    {
-        Self { f }
+            use extern "C" #[no_mangle];
+        }
+
+        pub(super) fn raise() {
+            let veneer: unsafe extern "C" fn() = ExternInt0;
+            unsafe { veneer() }
+        }
     }
 
-    /// Invokes the interrupt handler closure.
-    #[inline(always)]
-    pub fn invoke(&mut self) {
-        (self.f)();
+    #[test]
+    fn extern_c_veneer() {
+        test(|_| {
+            let mut i = 0;
+            handler!(handler = || i += 1);
+
+            scope(|scope| {
+                scope.register(extern_c::Interrupt::ExternInt0, handler);
+                extern_c::raise();
+            });
+
+            assert_eq!(i, 1);
+        })
     }
-}
 
-impl<'a> fmt::Debug for Handler<'a> {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "handler@{:p}", self as *const _)
+    scoped_interrupts! {
+        enum Aliased {
+            Renamed => AliasedInt0,
+        }
+
+        use #[no_mangle];
     }
-}
 
-/// Private API for use by the `scoped_interrupts!` macro. Do not use.
-#[doc(hidden)]
-pub struct HandlerAddr {
-    addr: AtomicUsize,
-}
+    #[test]
+    fn alias() {
+        test(|_| {
+            let mut i = 0;
+            handler!(handler = || i += 1);
 
-impl HandlerAddr {
-    #[inline(always)]
-    pub const fn new() -> Self {
-        Self {
-            addr: AtomicUsize::new(0),
-        }
+            scope(|scope| {
+                scope.register(Aliased::Renamed, handler);
+                unsafe { AliasedInt0() }
+            });
+
+            assert_eq!(i, 1);
+        })
     }
 
-    #[inline(always)]
-    pub fn load(&self) -> usize {
-        self.addr.load(Ordering::Acquire)
+    scoped_interrupts! {
+        enum PacInterrupt {
+            INT3,
+            Second => INT1 use #[inline(never)] #[cold],
+        }
+
+        impl From for mock_pac::interrupt;
+
+        use #[inline(never)] #[allow(unused_unsafe)];
     }
 
-    #[inline(always)]
-    pub unsafe fn store(&self, addr: usize) {
-        self.addr.store(addr, Ordering::Release)
+    #[test]
+    fn pac_conversion() {
+        assert_eq!(
+            mock_pac::interrupt::from(PacInterrupt::INT3),
+            mock_pac::interrupt::INT3
+        );
+        assert_eq!(
+            mock_pac::interrupt::from(PacInterrupt::Second),
+            mock_pac::interrupt::INT1
+        );
+        assert_eq!(PacInterrupt::INT3.irq_number(), 3);
+        assert_eq!(PacInterrupt::Second.irq_number(), 1);
     }
-}
 
-impl fmt::Debug for HandlerAddr {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "active handler@{:p}", self.load() as *const ())
+    static UNREGISTERED: AtomicUsize = AtomicUsize::new(0);
+
+    fn record_unregistered(interrupt: Unregistered) {
+        UNREGISTERED.store(interrupt as usize + 1, Ordering::SeqCst);
     }
-}
 
-/// Trait for interrupt enums generated by [`scoped_interrupts!`].
-///
-/// # Safety
-///
-/// This trait is unsafe to implement. Safely implementing it requires correctly implementing its
-/// methods. In particular, `deregister_all` must, in fact, deregister all registered handlers.
-///
-/// It is recommended to use [`scoped_interrupts!`] instead of implementing this trait by hand.
-///
-/// [`scoped_interrupts!`]: macro.scoped_interrupts.html
-pub unsafe trait Interrupt {
-    /// Registers a `handler` to handle interrupts of type `self`.
-    ///
-    /// # Safety
-    ///
-    /// This is only safe to call if the caller ensures that the handler is not invoked after its
-    /// lifetime expires.
-    unsafe fn register(self, handler: &mut Handler<'_>);
+    scoped_interrupts! {
+        enum Unregistered {
+            UnregInt0,
+            UnregInt1,
+        }
 
-    /// Deregisters all interrupts that were registered using `register`.
-    ///
-    /// This must reset the global interrupt state to its default/startup/reset values, where no
-    /// interrupt handlers are registered.
-    fn deregister_all();
-}
+        unregistered => call(record_unregistered);
 
-#[cfg(test)]
-mod tests {
-    use super::Interrupt as _;
-    use super::*;
-    use std::panic::catch_unwind;
+        use #[no_mangle];
+    }
 
     scoped_interrupts! {
-        enum Interrupt {
-            Int0,
-            Int1,
+        enum Ignored {
+            IgnoredInt0,
         }
 
+        unregistered => ignore;
+
         use #[no_mangle];
     }
 
-    struct Test {}
+    #[test]
+    fn unregistered_behavior() {
+        test(|_| {
+            unsafe { UnregInt1() }
+            assert_eq!(UNREGISTERED.load(Ordering::SeqCst), 2);
 
-    #[derive(Debug)]
-    struct Panicked {}
+            unsafe { IgnoredInt0() }
 
-    impl Test {
-        fn raise_interrupt(&mut self, int: Interrupt) -> Result<(), Panicked> {
-            catch_unwind(|| match int {
-                Interrupt::Int0 => unsafe { Int0() },
-                Interrupt::Int1 => unsafe { Int1() },
-            })
-            .map_err(|_| Panicked {})
+            let mut i = 0;
+            handler!(handler = || i += 1);
+            scope(|scope| {
+                scope.register(Ignored::IgnoredInt0, handler);
+                unsafe { IgnoredInt0() }
+            });
+            assert_eq!(i, 1);
+        })
+    }
+
+    macro_rules! bind_interrupt {
+        ($name:ident, $handler:path) => {
+            #[no_mangle]
+            pub unsafe extern "C" fn $name() {
+                let handler: fn() = $handler;
+                handler();
+            }
+        };
+    }
+
+    scoped_interrupts! {
+        enum HookMacro {
+            HookInt0,
+            Renamed => HookInt1,
         }
+
+        use bind_interrupt!(NAME, FN);
     }
 
-    impl Drop for Test {
-        fn drop(&mut self) {
-            // Reset
-            Interrupt::deregister_all();
+    #[test]
+    fn hook_macro() {
+        test(|_| {
+            let mut i = 0;
+            handler!(handler = || i += 1);
+
+            scope(|scope| {
+                scope.register(HookMacro::Renamed, handler);
+                unsafe { HookInt1::HookInt1() }
+            });
+
+            assert_eq!(i, 1);
+        })
+    }
+
+    // Declared in the same module as `Interrupt`.
+    scoped_interrupts! {
+        enum Virtual {
+            /// Never dispatched.
+            #[deprecated]
+            Virt0,
+            Virt1,
+        }
+
+        group ALL_VIRTUAL {
+            Virt0,
+            Virt1,
         }
     }
 
-    fn test(f: impl FnOnce(&mut Test)) {
-        // Lock a mutex for each test, ensuring that they run sequentially. This is required since
-        // they mutate shared state.
-        // Miri detects this as a leak (which I guess is kinda true), so don't do this on Miri. It
-        // doesn't support threads anyways.
-        #[cfg(not(miri))]
-        let _guard = {
-            use once_cell::sync::OnceCell;
-            use std::sync::Mutex;
+    scoped_interrupts! {
+        enum VirtualIgnored {
+            VirtIgnored0,
+            VirtIgnored1,
+        }
 
-            static MUTEX: OnceCell<Mutex<()>> = OnceCell::new();
-            MUTEX
-                .get_or_init(|| Mutex::new(()))
-                .lock()
-                .unwrap_or_else(|e| e.into_inner()) // drink the poison
-        };
+        unregistered => ignore;
 
-        let mut test = Test {};
+        subset VirtualIgnoredSubset {
+            VirtIgnored1,
+        }
+    }
 
-        f(&mut test);
+    #[test]
+    fn virtual_unregistered() {
+        test(|_| {
+            unsafe { VirtualIgnored::VirtIgnored0.dispatch() }
+
+            let mut i = 0;
+            handler!(handler = || i += 1);
+            scope(|scope| {
+                scope.register(VirtualIgnoredSubset::VirtIgnored1, handler);
+                unsafe { VirtualIgnored::VirtIgnored1.dispatch() }
+                unsafe { VirtualIgnoredSubset::VirtIgnored1.dispatch() }
+            });
+            assert_eq!(i, 2);
+        })
     }
 
     #[test]
-    fn not_registered() {
+    fn virtual_interrupts() {
         test(|test| {
-            test.raise_interrupt(Interrupt::Int0).unwrap_err();
-            test.raise_interrupt(Interrupt::Int1).unwrap_err();
-            test.raise_interrupt(Interrupt::Int0).unwrap_err();
-            test.raise_interrupt(Interrupt::Int1).unwrap_err();
-        });
+            let mut parent_calls = 0;
+            let mut virt_calls = 0;
+            handler!(virt1 = || virt_calls += 1);
+            handler!(
+                parent = || {
+                    parent_calls += 1;
+                    unsafe { Virtual::Virt1.dispatch() }
+                }
+            );
+
+            scope(|virt| {
+                virt.register(Virtual::Virt1, virt1);
+
+                scope(|scope| {
+                    scope.register(Interrupt::Int0, parent);
+                    test.raise_interrupt(Interrupt::Int0).unwrap();
+                    test.raise_interrupt(Interrupt::Int0).unwrap();
+                });
+            });
+
+            assert_eq!(parent_calls, 2);
+            assert_eq!(virt_calls, 2);
+        })
     }
 
     #[test]
-    fn simple() {
+    #[cfg(not(feature = "multi-core"))]
+    fn current() {
         test(|test| {
-            let mut i = 0;
+            let mut seen = None;
 
-            // Having to declare (store) the handler *outside* the call to `scope` is a natural
-            // property of this approach, since it might be called anytime until `scope` returns.
-            let mut closure = || {
-                i += 1;
-            };
-            let mut handler = Handler::new(&mut closure);
+            handler!(handler0 = || seen = Some(current_interrupt::<Interrupt>()));
 
-            // (this is verbose, normally one would use `handler!`)
+            assert!(current_interrupt::<Interrupt>().is_none());
 
             scope(|scope| {
-                // Non-'env handler would be unsound.
-                scope.register(Interrupt::Int0, &mut handler);
+                scope.register(Interrupt::Int0, handler0);
 
-                // Test that the handler is called when the interrupt is raised.
                 test.raise_interrupt(Interrupt::Int0).unwrap();
+                assert!(current_interrupt::<Interrupt>().is_none());
             });
 
-            assert_eq!(i, 1);
+            match seen {
+                Some(Some(Interrupt::Int0)) => {}
+                _ => panic!("handler did not observe `Int0` as the current interrupt"),
+            }
+        })
+    }
 
-            // Test that the end of the scope deregisters the interrupt.
-            test.raise_interrupt(Interrupt::Int0).unwrap_err();
-            assert_eq!(i, 1);
-        });
+    #[test]
+    #[cfg(not(feature = "multi-core"))]
+    fn current_across_enums() {
+        test(|test| {
+            let mut nested = None;
+            let mut outer = None;
+            handler!(
+                virt1 = || {
+                    nested = Some((
+                        current_interrupt::<Interrupt>().is_none(),
+                        matches!(current_interrupt::<Virtual>(), Some(Virtual::Virt1)),
+                    ))
+                }
+            );
+            handler!(
+                parent = || {
+                    unsafe { Virtual::Virt1.dispatch() }
+                    outer = Some(matches!(
+                        current_interrupt::<Interrupt>(),
+                        Some(Interrupt::Int0)
+                    ));
+                }
+            );
+
+            scope(|virt| {
+                virt.register(Virtual::Virt1, virt1);
+
+                scope(|scope| {
+                    scope.register(Interrupt::Int0, parent);
+                    test.raise_interrupt(Interrupt::Int0).unwrap();
+                });
+            });
+
+            assert_eq!(nested, Some((true, true)));
+            assert_eq!(outer, Some(true));
+        })
     }
 
     #[test]
-    fn handler_sharing_data() {
+    #[cfg(feature = "multi-core")]
+    fn current_multi_core() {
         test(|test| {
-            let shared = vec![0, 1, 2];
+            let mut seen = None;
+            handler!(
+                handler0 = || {
+                    assert_in_interrupt!(Interrupt::Int1);
+                    seen = Some(current_interrupt::<Interrupt>());
+                }
+            );
 
-            handler!(handler0 = || println!("{:?}", shared));
-            handler!(handler1 = || println!("{:?}", shared));
+            assert_in_interrupt!(Interrupt::Int0);
+
+            scope(|scope| {
+                scope.register(Interrupt::Int0, handler0);
+                test.raise_interrupt(Interrupt::Int0).unwrap();
+            });
+
+            assert!(matches!(seen, Some(None)));
+        })
+    }
+
+    #[test]
+    #[cfg(not(feature = "multi-core"))]
+    fn assert_in_interrupt() {
+        test(|test| {
+            handler!(handler0 = || assert_in_interrupt!(Interrupt::Int0));
+            handler!(handler1 = || assert_in_interrupt!(Interrupt::Int0));
+
+            catch_unwind(|| assert_in_interrupt!(Interrupt::Int0)).unwrap_err();
 
             scope(|scope| {
                 scope.register(Interrupt::Int0, handler0);
                 scope.register(Interrupt::Int1, handler1);
 
                 test.raise_interrupt(Interrupt::Int0).unwrap();
-                test.raise_interrupt(Interrupt::Int1).unwrap();
+                test.raise_interrupt(Interrupt::Int1).unwrap_err();
             });
         })
     }
@@ -0,0 +1,173 @@
+//! Support for testing code that uses scoped interrupts on the host.
+//!
+//! This module is only available when the `mock` Cargo feature is enabled. It allows invoking the
+//! handlers registered for the interrupts declared with [`scoped_interrupts!`] from test code, as
+//! if the interrupt had fired.
+//!
+//! On the host, there is no interrupt controller or vector table, so the veneers can be hooked
+//! with any attribute that is accepted on functions, such as `#[inline(never)]`. Declaring as many
+//! variants as needed provides the fake interrupts.
+//!
+//! The module does not depend on `std`, so it can also be used to run application logic in
+//! simulators on targets without interrupts at all, such as `wasm32-unknown-unknown`.
+//!
+//! Note that raising an interrupt that has no registered handler panics when debug assertions are
+//! enabled (as is the default for tests), and hangs otherwise.
+//!
+//! # Examples
+//!
+//! ```
+//! use irq::mock::Controller;
+//! use irq::{handler, scope, scoped_interrupts};
+//!
+//! scoped_interrupts! {
+//!     enum Interrupt {
+//!         UART0,
+//!         TIMER0,
+//!     }
+//!
+//!     use #[inline(never)];
+//! }
+//!
+//! fn main() {
+//!     let mut received = 0;
+//!     handler!(uart = || received += 1);
+//!
+//!     scope(|scope| {
+//!         scope.register(Interrupt::UART0, uart);
+//!
+//!         let mut nvic = Controller::new();
+//!         nvic.pend(Interrupt::UART0);
+//!         nvic.pend(Interrupt::UART0);
+//!         unsafe {
+//!             nvic.run_pending();
+//!         }
+//!     });
+//!
+//!     assert_eq!(received, 1);
+//! }
+//! ```
+//!
+//! [`scoped_interrupts!`]: ../macro.scoped_interrupts.html
+
+use crate::{Interrupt, InterruptSet};
+
+/// Invokes the handler registered for `interrupt`, as if the interrupt had fired.
+///
+/// # Safety
+///
+/// Interrupt handlers are not reentrant. This must not be called while the handler of `interrupt`
+/// is already running, for example from within that handler.
+pub unsafe fn raise<I: Interrupt>(interrupt: I) {
+    interrupt.dispatch();
+}
+
+/// A fake interrupt controller that keeps track of pending interrupts.
+///
+/// Like on real hardware, pending an interrupt that is already pending has no additional effect.
+#[derive(Debug)]
+pub struct Controller<I> {
+    pending: InterruptSet<I>,
+}
+
+impl<I: Interrupt> Controller<I> {
+    /// Creates a controller without any pending interrupts.
+    pub fn new() -> Self {
+        Self {
+            pending: InterruptSet::empty(),
+        }
+    }
+
+    /// Marks `interrupt` as pending.
+    pub fn pend(&mut self, interrupt: I) {
+        self.pending.insert(interrupt);
+    }
+
+    /// Clears the pending state of `interrupt`.
+    pub fn unpend(&mut self, interrupt: &I) {
+        self.pending.remove(interrupt);
+    }
+
+    /// Returns whether `interrupt` is pending.
+    pub fn is_pending(&self, interrupt: &I) -> bool {
+        self.pending.contains(interrupt)
+    }
+
+    /// Raises all pending interrupts, in order of their declaration, and clears their pending
+    /// state.
+    ///
+    /// # Safety
+    ///
+    /// This must not be called from within the handler of any of the pending interrupts (see
+    /// [`raise`]).
+    ///
+    /// [`raise`]: fn.raise.html
+    pub unsafe fn run_pending(&mut self) {
+        while let Some(interrupt) = self.pending.iter().next() {
+            self.pending.remove(&interrupt);
+            raise(interrupt);
+        }
+    }
+}
+
+impl<I: Interrupt> Default for Controller<I> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{handler, scope, scoped_interrupts};
+
+    scoped_interrupts! {
+        enum Interrupt {
+            MockInt0,
+            MockInt1,
+        }
+
+        use #[inline(never)];
+    }
+
+    #[test]
+    fn raise_invokes_handler() {
+        let mut calls = 0;
+        handler!(handler = || calls += 1);
+
+        scope(|scope| {
+            scope.register(Interrupt::MockInt1, handler);
+            unsafe {
+                raise(Interrupt::MockInt1);
+                raise(Interrupt::MockInt1);
+            }
+        });
+
+        assert_eq!(calls, 2);
+    }
+
+    #[test]
+    fn pending() {
+        let mut calls = 0;
+        handler!(handler = || calls += 1);
+
+        let mut controller = Controller::new();
+        controller.pend(Interrupt::MockInt1);
+        controller.pend(Interrupt::MockInt0);
+        controller.pend(Interrupt::MockInt0);
+        assert!(controller.is_pending(&Interrupt::MockInt0));
+        controller.unpend(&Interrupt::MockInt1);
+        assert!(!controller.is_pending(&Interrupt::MockInt1));
+
+        scope(|scope| {
+            scope.register(Interrupt::MockInt0, handler);
+            unsafe {
+                controller.run_pending();
+                controller.run_pending();
+            }
+        });
+
+        assert!(!controller.is_pending(&Interrupt::MockInt0));
+        assert_eq!(calls, 1);
+    }
+}
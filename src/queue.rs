@@ -0,0 +1,329 @@
+//! A bounded queue with multiple priority classes.
+
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::mem::MaybeUninit;
+use core::ptr;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// A bounded queue holding `C` priority classes of up to `N` items each.
+///
+/// Every class has its own producer, which is typically an interrupt handler. The consumer always
+/// dequeues from the highest-priority class that has any items in it, where class 0 has the
+/// highest priority. This is useful for eg. CAN frames, where frames with urgent IDs should be
+/// processed before bulk traffic.
+///
+/// When a class is full, new items for that class are rejected and counted. The count can be
+/// queried with [`ClassConsumer::dropped`].
+///
+/// Each class is a single-producer single-consumer ring buffer, so the queue only needs atomic
+/// loads and stores. `N` must be a power of two.
+///
+/// # Examples
+///
+/// ```
+/// use irq::{handler, scope, scoped_interrupts, ClassQueue};
+/// use mock_pac::interrupt;
+///
+/// scoped_interrupts! {
+///     enum Interrupt {
+///         INT0,
+///         INT1,
+///     }
+///
+///     use #[interrupt];
+/// }
+///
+/// # fn read_frame() -> u32 { 0 }
+/// fn main() {
+///     let mut queue = ClassQueue::<u32, 2, 16>::new();
+///     let (mut producers, mut consumer) = queue.split();
+///     let mut urgent = producers.take(0).unwrap();
+///     let mut bulk = producers.take(1).unwrap();
+///
+///     handler!(can_rx0 = || {
+///         let _ = urgent.enqueue(read_frame());
+///     });
+///     handler!(can_rx1 = || {
+///         let _ = bulk.enqueue(read_frame());
+///     });
+///
+///     scope(|scope| {
+///         scope.register(Interrupt::INT0, can_rx0);
+///         scope.register(Interrupt::INT1, can_rx1);
+///
+///         loop {
+///             while let Some((class, frame)) = consumer.dequeue() {
+///                 // Process the frame...
+/// # let _ = (class, frame);
+///             }
+/// # break;
+///         }
+///     });
+/// }
+/// ```
+///
+/// [`ClassConsumer::dropped`]: struct.ClassConsumer.html#method.dropped
+pub struct ClassQueue<T, const C: usize, const N: usize> {
+    classes: [Ring<T, N>; C],
+}
+
+/// A single-producer single-consumer ring buffer.
+struct Ring<T, const N: usize> {
+    slots: [UnsafeCell<MaybeUninit<T>>; N],
+    /// Number of items dequeued so far. Only written by the consumer.
+    head: AtomicUsize,
+    /// Number of items enqueued so far. Only written by the producer.
+    tail: AtomicUsize,
+    /// Number of items rejected because the ring was full. Only written by the producer.
+    dropped: AtomicUsize,
+}
+
+// Safety: Values of type `T` are sent from the producer to the consumer. Every slot is only
+// accessed by either the producer or the consumer, as determined by `head` and `tail`.
+unsafe impl<T: Send, const N: usize> Sync for Ring<T, N> {}
+
+impl<T, const N: usize> Ring<T, N> {
+    #[allow(clippy::declare_interior_mutable_const)]
+    const EMPTY_SLOT: UnsafeCell<MaybeUninit<T>> = UnsafeCell::new(MaybeUninit::uninit());
+
+    /// Used to initialize the class array, so every use creating a new ring is intended.
+    #[allow(clippy::declare_interior_mutable_const)]
+    const NEW: Self = Self {
+        slots: [Self::EMPTY_SLOT; N],
+        head: AtomicUsize::new(0),
+        tail: AtomicUsize::new(0),
+        dropped: AtomicUsize::new(0),
+    };
+
+    fn enqueue(&self, value: T) -> Result<(), T> {
+        let tail = self.tail.load(Ordering::Relaxed);
+        let head = self.head.load(Ordering::Acquire);
+        if tail.wrapping_sub(head) == N {
+            let dropped = self.dropped.load(Ordering::Relaxed);
+            self.dropped
+                .store(dropped.saturating_add(1), Ordering::Relaxed);
+            return Err(value);
+        }
+
+        unsafe {
+            ptr::write(self.slots[tail % N].get(), MaybeUninit::new(value));
+        }
+        self.tail.store(tail.wrapping_add(1), Ordering::Release);
+        Ok(())
+    }
+
+    fn dequeue(&self) -> Option<T> {
+        let head = self.head.load(Ordering::Relaxed);
+        let tail = self.tail.load(Ordering::Acquire);
+        if head == tail {
+            return None;
+        }
+
+        let value = unsafe { ptr::read(self.slots[head % N].get()).assume_init() };
+        self.head.store(head.wrapping_add(1), Ordering::Release);
+        Some(value)
+    }
+}
+
+impl<T, const C: usize, const N: usize> ClassQueue<T, C, N> {
+    /// Forces a compile-time error when `N` is not a power of two.
+    ///
+    /// This ensures that the item counters map to consecutive slots, even when they wrap around.
+    const POWER_OF_TWO: () = assert!(
+        N.is_power_of_two(),
+        "`ClassQueue` capacity must be a power of two"
+    );
+
+    /// Creates a new, empty queue.
+    pub const fn new() -> Self {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::POWER_OF_TWO;
+        Self {
+            classes: [Ring::NEW; C],
+        }
+    }
+
+    /// Splits the queue into its producers and its consumer.
+    pub fn split(&mut self) -> (ClassProducers<'_, T, C, N>, ClassConsumer<'_, T, C, N>) {
+        (
+            ClassProducers {
+                queue: self,
+                taken: [false; C],
+            },
+            ClassConsumer { queue: self },
+        )
+    }
+}
+
+impl<T, const C: usize, const N: usize> Default for ClassQueue<T, C, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const C: usize, const N: usize> Drop for ClassQueue<T, C, N> {
+    fn drop(&mut self) {
+        for ring in &self.classes {
+            while ring.dequeue().is_some() {}
+        }
+    }
+}
+
+impl<T, const C: usize, const N: usize> fmt::Debug for ClassQueue<T, C, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ClassQueue")
+            .field("classes", &C)
+            .field("capacity", &N)
+            .finish()
+    }
+}
+
+/// Hands out the producers of the classes of a [`ClassQueue`].
+///
+/// [`ClassQueue`]: struct.ClassQueue.html
+#[derive(Debug)]
+pub struct ClassProducers<'a, T, const C: usize, const N: usize> {
+    queue: &'a ClassQueue<T, C, N>,
+    taken: [bool; C],
+}
+
+impl<'a, T, const C: usize, const N: usize> ClassProducers<'a, T, C, N> {
+    /// Takes the producer of `class`.
+    ///
+    /// Returns `None` if `class` is out of range, or if its producer was already taken.
+    pub fn take(&mut self, class: usize) -> Option<ClassProducer<'a, T, N>> {
+        let taken = self.taken.get_mut(class)?;
+        if *taken {
+            return None;
+        }
+
+        *taken = true;
+        Some(ClassProducer {
+            ring: &self.queue.classes[class],
+        })
+    }
+}
+
+/// The producer of a single class of a [`ClassQueue`].
+///
+/// [`ClassQueue`]: struct.ClassQueue.html
+pub struct ClassProducer<'a, T, const N: usize> {
+    ring: &'a Ring<T, N>,
+}
+
+impl<'a, T, const N: usize> ClassProducer<'a, T, N> {
+    /// Adds `value` to the end of this class.
+    ///
+    /// If the class is full, `value` is returned back as an error, and the class's dropped count
+    /// is incremented.
+    pub fn enqueue(&mut self, value: T) -> Result<(), T> {
+        self.ring.enqueue(value)
+    }
+}
+
+impl<'a, T, const N: usize> fmt::Debug for ClassProducer<'a, T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ClassProducer").finish()
+    }
+}
+
+/// The consumer of a [`ClassQueue`].
+///
+/// [`ClassQueue`]: struct.ClassQueue.html
+#[derive(Debug)]
+pub struct ClassConsumer<'a, T, const C: usize, const N: usize> {
+    queue: &'a ClassQueue<T, C, N>,
+}
+
+impl<'a, T, const C: usize, const N: usize> ClassConsumer<'a, T, C, N> {
+    /// Removes the oldest item of the highest-priority non-empty class.
+    ///
+    /// Returns the class the item was taken from, along with the item, or `None` if all classes
+    /// are empty.
+    pub fn dequeue(&mut self) -> Option<(usize, T)> {
+        self.queue
+            .classes
+            .iter()
+            .enumerate()
+            .find_map(|(class, ring)| ring.dequeue().map(|value| (class, value)))
+    }
+
+    /// Returns the number of items that were rejected because `class` was full.
+    ///
+    /// Returns 0 if `class` is out of range.
+    pub fn dropped(&self, class: usize) -> usize {
+        self.queue
+            .classes
+            .get(class)
+            .map_or(0, |ring| ring.dropped.load(Ordering::Relaxed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn highest_class_first() {
+        let mut queue = ClassQueue::<u32, 3, 4>::new();
+        let (mut producers, mut consumer) = queue.split();
+        let mut low = producers.take(2).unwrap();
+        let mut high = producers.take(0).unwrap();
+
+        low.enqueue(20).unwrap();
+        low.enqueue(21).unwrap();
+        high.enqueue(0).unwrap();
+
+        assert_eq!(consumer.dequeue(), Some((0, 0)));
+        assert_eq!(consumer.dequeue(), Some((2, 20)));
+        high.enqueue(1).unwrap();
+        assert_eq!(consumer.dequeue(), Some((0, 1)));
+        assert_eq!(consumer.dequeue(), Some((2, 21)));
+        assert_eq!(consumer.dequeue(), None);
+    }
+
+    #[test]
+    fn take_once() {
+        let mut queue = ClassQueue::<u32, 2, 4>::new();
+        let (mut producers, _) = queue.split();
+        assert!(producers.take(1).is_some());
+        assert!(producers.take(1).is_none());
+        assert!(producers.take(2).is_none());
+    }
+
+    #[test]
+    fn overflow() {
+        let mut queue = ClassQueue::<u32, 2, 2>::new();
+        let (mut producers, mut consumer) = queue.split();
+        let mut producer = producers.take(1).unwrap();
+
+        producer.enqueue(1).unwrap();
+        producer.enqueue(2).unwrap();
+        assert_eq!(producer.enqueue(3), Err(3));
+        assert_eq!(producer.enqueue(4), Err(4));
+        assert_eq!(consumer.dropped(0), 0);
+        assert_eq!(consumer.dropped(1), 2);
+
+        assert_eq!(consumer.dequeue(), Some((1, 1)));
+        producer.enqueue(5).unwrap();
+        assert_eq!(consumer.dequeue(), Some((1, 2)));
+        assert_eq!(consumer.dequeue(), Some((1, 5)));
+        assert_eq!(consumer.dequeue(), None);
+    }
+
+    #[test]
+    fn drops_remaining_items() {
+        use std::rc::Rc;
+
+        let item = Rc::new(());
+        {
+            let mut queue = ClassQueue::<Rc<()>, 1, 2>::new();
+            let (mut producers, _) = queue.split();
+            let mut producer = producers.take(0).unwrap();
+            producer.enqueue(item.clone()).unwrap();
+            assert_eq!(Rc::strong_count(&item), 2);
+        }
+        assert_eq!(Rc::strong_count(&item), 1);
+    }
+}
@@ -3,7 +3,8 @@
 macro_rules! doc {
     ($e:expr) => {
         #[doc = $e]
-        extern {}
+        #[allow(unused_doc_comments)]
+        extern "C" {}
     };
 }
 
@@ -0,0 +1,389 @@
+//! A set of hooked interrupts.
+
+use crate::Interrupt;
+use core::fmt;
+use core::iter::{FromIterator, FusedIterator};
+use core::marker::PhantomData;
+use core::ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, Sub, SubAssign};
+
+/// A set of interrupts of an enum generated by [`scoped_interrupts!`].
+///
+/// The set is stored as a bitmask, so all operations are cheap. It can hold interrupt enums with up
+/// to [`CAPACITY`] variants; using it with a larger enum results in a compile-time error.
+///
+/// # Examples
+///
+/// ```
+/// use irq::{scoped_interrupts, InterruptSet};
+/// use mock_pac::interrupt;
+///
+/// scoped_interrupts! {
+///     enum Interrupt {
+///         INT0,
+///         INT1,
+///         INT2,
+///     }
+///
+///     use #[interrupt];
+/// }
+///
+/// fn main() {
+///     let radio = InterruptSet::from(Interrupt::INT0) | Interrupt::INT1.into();
+///     let timers = InterruptSet::from(Interrupt::INT1) | Interrupt::INT2.into();
+///
+///     assert!((radio & timers).contains(&Interrupt::INT1));
+///     assert_eq!((radio | timers).len(), 3);
+///     assert_eq!(radio | timers, InterruptSet::all());
+/// }
+/// ```
+///
+/// [`scoped_interrupts!`]: macro.scoped_interrupts.html
+/// [`CAPACITY`]: #associatedconstant.CAPACITY
+pub struct InterruptSet<I> {
+    bits: u64,
+    _p: PhantomData<fn() -> I>,
+}
+
+impl<I: Interrupt> InterruptSet<I> {
+    /// The maximum number of interrupt enum variants supported by `InterruptSet`.
+    pub const CAPACITY: usize = 64;
+
+    /// Forces a compile-time error when `I` has too many variants to fit into the set.
+    const FITS: () = assert!(
+        I::COUNT <= Self::CAPACITY,
+        "interrupt enum has too many variants to be used with `InterruptSet`"
+    );
+
+    /// Creates an empty set.
+    #[inline]
+    pub const fn empty() -> Self {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::FITS;
+        Self::from_bits(0)
+    }
+
+    /// Creates a set containing every interrupt of `I`.
+    #[inline]
+    pub const fn all() -> Self {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::FITS;
+        if I::COUNT == Self::CAPACITY {
+            Self::from_bits(!0)
+        } else {
+            Self::from_bits((1 << I::COUNT) - 1)
+        }
+    }
+
+    /// Private API for use by the `scoped_interrupts!` macro. Do not use.
+    #[doc(hidden)]
+    pub const fn __from_indices(indices: &[usize]) -> Self {
+        let mut bits = 0;
+        let mut i = 0;
+        while i < indices.len() {
+            bits |= 1 << indices[i];
+            i += 1;
+        }
+        Self::from_bits(bits)
+    }
+
+    #[inline]
+    const fn from_bits(bits: u64) -> Self {
+        Self {
+            bits,
+            _p: PhantomData,
+        }
+    }
+
+    #[inline]
+    fn bit(interrupt: &I) -> u64 {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::FITS;
+        1 << interrupt.index()
+    }
+
+    /// Adds `interrupt` to the set.
+    ///
+    /// Returns whether the interrupt was newly added, ie. `false` if it already was in the set.
+    #[inline]
+    pub fn insert(&mut self, interrupt: I) -> bool {
+        let bit = Self::bit(&interrupt);
+        let added = self.bits & bit == 0;
+        self.bits |= bit;
+        added
+    }
+
+    /// Removes `interrupt` from the set.
+    ///
+    /// Returns whether the interrupt was in the set.
+    #[inline]
+    pub fn remove(&mut self, interrupt: &I) -> bool {
+        let bit = Self::bit(interrupt);
+        let removed = self.bits & bit != 0;
+        self.bits &= !bit;
+        removed
+    }
+
+    /// Returns whether `interrupt` is in the set.
+    #[inline]
+    pub fn contains(&self, interrupt: &I) -> bool {
+        self.bits & Self::bit(interrupt) != 0
+    }
+
+    /// Returns whether the set is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.bits == 0
+    }
+
+    /// Returns the number of interrupts in the set.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.bits.count_ones() as usize
+    }
+
+    /// Returns the set of interrupts contained in `self` or `other`.
+    #[inline]
+    pub fn union(self, other: Self) -> Self {
+        Self::from_bits(self.bits | other.bits)
+    }
+
+    /// Returns the set of interrupts contained in both `self` and `other`.
+    #[inline]
+    pub fn intersection(self, other: Self) -> Self {
+        Self::from_bits(self.bits & other.bits)
+    }
+
+    /// Returns the set of interrupts contained in `self`, but not in `other`.
+    #[inline]
+    pub fn difference(self, other: Self) -> Self {
+        Self::from_bits(self.bits & !other.bits)
+    }
+
+    /// Returns an iterator over the interrupts in the set, in ascending order of their index.
+    #[inline]
+    pub fn iter(&self) -> InterruptSetIter<I> {
+        InterruptSetIter {
+            bits: self.bits,
+            _p: PhantomData,
+        }
+    }
+}
+
+// Manual impls, since the derives would put unnecessary bounds on `I`.
+
+impl<I> Clone for InterruptSet<I> {
+    #[inline]
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<I> Copy for InterruptSet<I> {}
+
+impl<I> PartialEq for InterruptSet<I> {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.bits == other.bits
+    }
+}
+
+impl<I> Eq for InterruptSet<I> {}
+
+impl<I: Interrupt> Default for InterruptSet<I> {
+    #[inline]
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+impl<I> fmt::Debug for InterruptSet<I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "InterruptSet({:#b})", self.bits)
+    }
+}
+
+impl<I: Interrupt> From<I> for InterruptSet<I> {
+    #[inline]
+    fn from(interrupt: I) -> Self {
+        Self::from_bits(Self::bit(&interrupt))
+    }
+}
+
+impl<I: Interrupt> FromIterator<I> for InterruptSet<I> {
+    fn from_iter<T: IntoIterator<Item = I>>(iter: T) -> Self {
+        let mut set = Self::empty();
+        set.extend(iter);
+        set
+    }
+}
+
+impl<I: Interrupt> Extend<I> for InterruptSet<I> {
+    fn extend<T: IntoIterator<Item = I>>(&mut self, iter: T) {
+        for interrupt in iter {
+            self.insert(interrupt);
+        }
+    }
+}
+
+impl<I: Interrupt> IntoIterator for InterruptSet<I> {
+    type Item = I;
+    type IntoIter = InterruptSetIter<I>;
+
+    #[inline]
+    fn into_iter(self) -> InterruptSetIter<I> {
+        self.iter()
+    }
+}
+
+impl<I: Interrupt> BitOr for InterruptSet<I> {
+    type Output = Self;
+
+    #[inline]
+    fn bitor(self, rhs: Self) -> Self {
+        self.union(rhs)
+    }
+}
+
+impl<I: Interrupt> BitOrAssign for InterruptSet<I> {
+    #[inline]
+    fn bitor_assign(&mut self, rhs: Self) {
+        *self = self.union(rhs);
+    }
+}
+
+impl<I: Interrupt> BitAnd for InterruptSet<I> {
+    type Output = Self;
+
+    #[inline]
+    fn bitand(self, rhs: Self) -> Self {
+        self.intersection(rhs)
+    }
+}
+
+impl<I: Interrupt> BitAndAssign for InterruptSet<I> {
+    #[inline]
+    fn bitand_assign(&mut self, rhs: Self) {
+        *self = self.intersection(rhs);
+    }
+}
+
+impl<I: Interrupt> Sub for InterruptSet<I> {
+    type Output = Self;
+
+    #[inline]
+    fn sub(self, rhs: Self) -> Self {
+        self.difference(rhs)
+    }
+}
+
+impl<I: Interrupt> SubAssign for InterruptSet<I> {
+    #[inline]
+    fn sub_assign(&mut self, rhs: Self) {
+        *self = self.difference(rhs);
+    }
+}
+
+/// Iterator over the interrupts in an [`InterruptSet`].
+///
+/// [`InterruptSet`]: struct.InterruptSet.html
+pub struct InterruptSetIter<I> {
+    bits: u64,
+    _p: PhantomData<fn() -> I>,
+}
+
+impl<I: Interrupt> Iterator for InterruptSetIter<I> {
+    type Item = I;
+
+    fn next(&mut self) -> Option<I> {
+        if self.bits == 0 {
+            return None;
+        }
+
+        let index = self.bits.trailing_zeros();
+        self.bits &= !(1 << index);
+        I::from_index(index as usize)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.bits.count_ones() as usize;
+        (len, Some(len))
+    }
+}
+
+impl<I: Interrupt> ExactSizeIterator for InterruptSetIter<I> {}
+
+impl<I: Interrupt> FusedIterator for InterruptSetIter<I> {}
+
+impl<I> fmt::Debug for InterruptSetIter<I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "InterruptSetIter({:#b})", self.bits)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::InterruptSet;
+    use crate::scoped_interrupts;
+
+    scoped_interrupts! {
+        #[derive(Debug, PartialEq)]
+        enum Interrupt {
+            SetInt0,
+            SetInt1,
+            SetInt2,
+        }
+
+        use #[no_mangle];
+    }
+
+    #[test]
+    fn insert_remove() {
+        let mut set = InterruptSet::empty();
+        assert!(set.is_empty());
+
+        assert!(set.insert(Interrupt::SetInt1));
+        assert!(!set.insert(Interrupt::SetInt1));
+        assert!(set.contains(&Interrupt::SetInt1));
+        assert!(!set.contains(&Interrupt::SetInt0));
+        assert_eq!(set.len(), 1);
+
+        assert!(set.remove(&Interrupt::SetInt1));
+        assert!(!set.remove(&Interrupt::SetInt1));
+        assert!(set.is_empty());
+    }
+
+    #[test]
+    fn set_operations() {
+        let a: InterruptSet<_> = vec![Interrupt::SetInt0, Interrupt::SetInt1]
+            .into_iter()
+            .collect();
+        let b: InterruptSet<_> = vec![Interrupt::SetInt1, Interrupt::SetInt2]
+            .into_iter()
+            .collect();
+
+        assert_eq!(a | b, InterruptSet::all());
+        assert_eq!(a & b, InterruptSet::from(Interrupt::SetInt1));
+        assert_eq!(a - b, InterruptSet::from(Interrupt::SetInt0));
+        assert_eq!(InterruptSet::<Interrupt>::all().len(), 3);
+    }
+
+    #[test]
+    fn iter() {
+        let set = InterruptSet::from(Interrupt::SetInt2) | Interrupt::SetInt0.into();
+        assert_eq!(
+            set.iter().collect::<Vec<_>>(),
+            [Interrupt::SetInt0, Interrupt::SetInt2]
+        );
+        assert_eq!(InterruptSet::<Interrupt>::empty().iter().next(), None);
+
+        let mut iter = set.iter();
+        assert_eq!(iter.len(), 2);
+        iter.next();
+        assert_eq!(iter.len(), 1);
+        iter.next();
+        assert_eq!(iter.len(), 0);
+        assert_eq!(iter.next(), None);
+        assert_eq!(iter.next(), None);
+    }
+}
@@ -0,0 +1,19 @@
+//! Test that `#[cfg]` on a variant is rejected, since it would not be applied to the code generated
+//! for that variant.
+
+#![allow(warnings)]
+
+use irq::scoped_interrupts;
+use mock_pac::interrupt;
+
+scoped_interrupts! {
+    enum Interrupt {
+        INT0,
+        #[cfg(any())]
+        INT1,
+    }
+
+    use #[interrupt];
+}
+
+fn main() {}
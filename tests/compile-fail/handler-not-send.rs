@@ -0,0 +1,27 @@
+//! Tests that handler closures must be `Send`.
+//!
+//! Handlers run in interrupt context, and on multi-core systems possibly on a different core than
+//! the one that registered them, so they must not capture data that is confined to one thread.
+
+#![allow(warnings)]
+
+use irq::{handler, scope, scoped_interrupts};
+use mock_pac::interrupt;
+use std::rc::Rc;
+
+scoped_interrupts! {
+    enum Interrupt {
+        INT0,
+    }
+
+    use #[interrupt];
+}
+
+fn main() {
+    let counter = Rc::new(0);
+    handler!(int0 = move || drop(counter.clone()));
+
+    scope(|scope| {
+        scope.register(Interrupt::INT0, int0);
+    });
+}